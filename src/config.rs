@@ -0,0 +1,365 @@
+//! User settings persisted to `config.toml` in the squeal config directory.
+//!
+//! Only the subset of TOML squeal writes is understood: `[section]` headers and `key = value`
+//! pairs whose values are strings, integers, floats, or booleans. Saving rewrites only the keys
+//! squeal changed, so comments and the order of the existing file are kept.
+
+use std::{fmt, fs, path::Path};
+
+use anyhow::{Context, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => {
+                f.write_str("\"")?;
+                for ch in s.chars() {
+                    match ch {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        _ => write!(f, "{}", ch)?,
+                    }
+                }
+                f.write_str("\"")
+            },
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) if x.fract() == 0.0 && x.is_finite() => write!(f, "{:.1}", x),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Section {
+    name: String,
+    entries: Vec<(String, Value)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    sections: Vec<Section>,
+}
+
+impl Config {
+    /// Reads `path`, treating a missing file as an empty config.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// Writes the config over the file at `path`, keeping its comments (see `render_over`).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let original = if path.exists() {
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+        } else {
+            String::new()
+        };
+        fs::write(path, self.render_over(&original))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// `original` with each key's value replaced by this config's, keys this config lacks
+    /// dropped, and new keys added at the end of their section (or in a new section at the end).
+    /// Comments, blank lines, and order are left alone.
+    pub fn render_over(&self, original: &str) -> String {
+        let mut out: Vec<String> = Vec::new();
+        let mut written: Vec<(String, String)> = Vec::new();
+        let mut section = String::new();
+        let mut sections_seen = vec![String::new()];
+        for raw in original.lines() {
+            let code = strip_comment(raw);
+            let line = code.trim();
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                self.append_missing(&section, &written, &mut out);
+                section = name.trim().to_string();
+                sections_seen.push(section.clone());
+                out.push(raw.to_string());
+                continue;
+            }
+            let Some((key, _)) = line.split_once('=') else {
+                out.push(raw.to_string());
+                continue;
+            };
+            let key = key.trim();
+            let done = written.iter().any(|(s, k)| *s == section && k == key);
+            if let Some(value) = self.get(&section, key).filter(|_| !done) {
+                let comment = &raw[code.len()..];
+                let indent = &raw[..raw.len() - raw.trim_start().len()];
+                let gap = if comment.is_empty() { "" } else { " " };
+                out.push(format!("{}{} = {}{}{}", indent, key, value, gap, comment));
+                written.push((section.clone(), key.to_string()));
+            }
+        }
+        self.append_missing(&section, &written, &mut out);
+        for new in self.sections.iter().filter(|s| !sections_seen.contains(&s.name)) {
+            if out.last().is_some_and(|l| !l.trim().is_empty()) {
+                out.push(String::new());
+            }
+            out.push(format!("[{}]", new.name));
+            out.extend(new.entries.iter().map(|(k, v)| format!("{} = {}", k, v)));
+        }
+        let mut text = out.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Adds the keys of `section` not yet in `written` after the section's last header or entry,
+    /// so comments introducing the next section stay with it.
+    fn append_missing(&self, section: &str, written: &[(String, String)], out: &mut Vec<String>) {
+        let at = out
+            .iter()
+            .rposition(|l| !strip_comment(l).trim().is_empty())
+            .map_or(out.len(), |i| i + 1);
+        let missing: Vec<String> = self
+            .entries(section)
+            .filter(|(k, _)| !written.iter().any(|(s, w)| s == section && w == k))
+            .map(|(k, v)| format!("{} = {}", k, v))
+            .collect();
+        out.splice(at..at, missing);
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut section = String::new();
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("line {}: unterminated section header", line_no))?;
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", line_no))?;
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(format!("line {}: missing key", line_no));
+            }
+            let value =
+                parse_value(value.trim()).map_err(|e| format!("line {}: {}", line_no, e))?;
+            config.set(&section, key, value);
+        }
+        Ok(config)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&Value> {
+        self.sections
+            .iter()
+            .find(|s| s.name == section)?
+            .entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn set(&mut self, section: &str, key: &str, value: Value) {
+        let idx = match self.sections.iter().position(|s| s.name == section) {
+            Some(idx) => idx,
+            None => {
+                self.sections.push(Section { name: section.to_string(), entries: Vec::new() });
+                self.sections.len() - 1
+            },
+        };
+        let entries = &mut self.sections[idx].entries;
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, existing)) => *existing = value,
+            None => entries.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn remove(&mut self, section: &str, key: &str) {
+        if let Some(s) = self.sections.iter_mut().find(|s| s.name == section) {
+            s.entries.retain(|(k, _)| k != key);
+        }
+        self.sections.retain(|s| !s.entries.is_empty());
+    }
+
+    /// Keys and values of `section` in file order.
+    pub fn entries(&self, section: &str) -> impl Iterator<Item = (&str, &Value)> {
+        self.sections
+            .iter()
+            .filter(move |s| s.name == section)
+            .flat_map(|s| s.entries.iter().map(|(k, v)| (k.as_str(), v)))
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Root keys must come before any header or they'd land in the previous section
+        let mut sections: Vec<&Section> = self.sections.iter().collect();
+        sections.sort_by_key(|s| !s.name.is_empty());
+        for (i, section) in sections.iter().enumerate() {
+            if !section.name.is_empty() {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                writeln!(f, "[{}]", section.name)?;
+            }
+            for (key, value) in &section.entries {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {},
+        }
+    }
+    line
+}
+
+fn parse_value(raw: &str) -> Result<Value, String> {
+    if let Some(body) = raw.strip_prefix('"') {
+        let body = body.strip_suffix('"').ok_or("unterminated string")?;
+        let mut out = String::new();
+        let mut chars = body.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                out.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                other => return Err(format!("unsupported escape \\{}", other.unwrap_or(' '))),
+            }
+        }
+        return Ok(Value::String(out));
+    }
+    match raw {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => {},
+    }
+    let numeric = raw.replace('_', "");
+    if let Ok(i) = numeric.parse::<i64>() {
+        return Ok(Value::Integer(i));
+    }
+    if let Ok(x) = numeric.parse::<f64>() {
+        return Ok(Value::Float(x));
+    }
+    Err(format!("unsupported value `{}`", raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sections_values_and_comments() {
+        let config = Config::parse(
+            "# squeal settings\ntop = 1\n[pragmas]\nforeign_keys = \"ON\" # trailing\n[ui]\nratio \
+             = 0.5\nzebra = false\nlabel = \"a \\\"quoted\\\" # not a comment\"\n",
+        )
+        .expect("config should parse");
+        assert_eq!(config.get("", "top"), Some(&Value::Integer(1)));
+        assert_eq!(config.get("pragmas", "foreign_keys"), Some(&Value::String("ON".to_string())));
+        assert_eq!(config.get("ui", "ratio"), Some(&Value::Float(0.5)));
+        assert_eq!(config.get("ui", "zebra"), Some(&Value::Boolean(false)));
+        assert_eq!(
+            config.get("ui", "label"),
+            Some(&Value::String("a \"quoted\" # not a comment".to_string()))
+        );
+    }
+
+    #[test]
+    fn roundtrips_through_display() {
+        let mut config = Config::default();
+        config.set("pragmas", "cache_size", Value::Integer(-8000));
+        config.set("", "name", Value::String("x\\y\n".to_string()));
+        config.set("ui", "ratio", Value::Float(2.0));
+        let reparsed = Config::parse(&config.to_string()).expect("output should parse");
+        assert_eq!(reparsed.get("pragmas", "cache_size"), Some(&Value::Integer(-8000)));
+        assert_eq!(reparsed.get("", "name"), Some(&Value::String("x\\y\n".to_string())));
+        assert_eq!(reparsed.get("ui", "ratio"), Some(&Value::Float(2.0)));
+
+        config.remove("pragmas", "cache_size");
+        assert_eq!(config.entries("pragmas").count(), 0);
+    }
+
+    #[test]
+    fn saving_keeps_comments_and_order() {
+        let original = "# squeal settings\n[results]\nzebra = false # stripes\nold = 1\n\n# \
+                        pragmas\n[pragmas]\nforeign_keys = \"ON\"\n";
+        let mut config = Config::parse(original).expect("config should parse");
+        config.set("results", "zebra", Value::Boolean(true));
+        config.set("results", "row_separators", Value::Boolean(true));
+        config.remove("results", "old");
+        config.set("layout", "editor_percent", Value::Integer(40));
+        assert_eq!(
+            config.render_over(original),
+            "# squeal settings\n[results]\nzebra = true # stripes\nrow_separators = true\n\n# \
+             pragmas\n[pragmas]\nforeign_keys = \"ON\"\n\n[layout]\neditor_percent = 40\n"
+        );
+        assert_eq!(Config::parse(&config.render_over("")).expect("output should parse"), config);
+    }
+
+    #[test]
+    fn reports_line_of_invalid_entry() {
+        let err = Config::parse("[ui]\nzebra = maybe\n").expect_err("value should be rejected");
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+}
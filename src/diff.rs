@@ -0,0 +1,67 @@
+//! Line diff used to compare queries from history.
+
+#[derive(Debug, PartialEq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Longest-common-subsequence diff of `old` and `new` by line. Queries are short, so the
+/// quadratic table is fine.
+pub fn line_diff<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            out.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            out.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    out.extend(old[i..].iter().map(|l| DiffLine::Removed(l)));
+    out.extend(new[j..].iter().map(|l| DiffLine::Added(l)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_changed_and_appended_lines() {
+        let diff =
+            line_diff("select a\nfrom t\nwhere x = 1", "select a, b\nfrom t\nwhere x = 1\nlimit 5");
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Removed("select a"),
+                DiffLine::Added("select a, b"),
+                DiffLine::Same("from t"),
+                DiffLine::Same("where x = 1"),
+                DiffLine::Added("limit 5"),
+            ]
+        );
+        assert!(line_diff("same", "same").iter().all(|l| matches!(l, DiffLine::Same(_))));
+    }
+}
@@ -0,0 +1,154 @@
+//! Serializes a result set to CSV (RFC 4180) or JSON for `:export`.
+//!
+//! NULL stays distinct from the text `NULL`: CSV writes an empty unquoted field (an empty string
+//! is written as `""`), JSON writes `null`. Blobs are written as lowercase hex.
+
+use std::fmt::Write;
+
+use crate::value::CellValue;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+
+    pub fn render(self, headers: &[String], rows: &[Vec<CellValue>]) -> String {
+        match self {
+            Self::Csv => to_csv(headers, rows),
+            Self::Json => to_json(headers, rows),
+        }
+    }
+}
+
+pub fn to_csv(headers: &[String], rows: &[Vec<CellValue>]) -> String {
+    let mut out = String::new();
+    let header_fields: Vec<String> = headers.iter().map(|h| csv_text(h)).collect();
+    out.push_str(&header_fields.join(","));
+    out.push_str("\r\n");
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|cell| match cell {
+                CellValue::Null => String::new(),
+                CellValue::Text(s) => csv_text(s),
+                CellValue::Blob(b) => hex(b),
+                other => other.display().into_owned(),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Quotes a field when it is empty or contains a separator, quote, or line break.
+fn csv_text(s: &str) -> String {
+    if s.is_empty() || s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn to_json(headers: &[String], rows: &[Vec<CellValue>]) -> String {
+    let mut out = String::from("[");
+    for (i, row) in rows.iter().enumerate() {
+        out.push_str(if i == 0 { "\n  {" } else { ",\n  {" });
+        for (j, (header, cell)) in headers.iter().zip(row).enumerate() {
+            if j > 0 {
+                out.push_str(", ");
+            }
+            json_string(&mut out, header);
+            out.push_str(": ");
+            match cell {
+                CellValue::Null => out.push_str("null"),
+                CellValue::Integer(i) => out.push_str(&i.to_string()),
+                CellValue::Real(f) if f.is_finite() => out.push_str(&f.to_string()),
+                CellValue::Real(_) => out.push_str("null"),
+                CellValue::Text(s) => json_string(&mut out, s),
+                CellValue::Blob(b) => json_string(&mut out, &hex(b)),
+            }
+        }
+        out.push('}');
+    }
+    out.push_str(if rows.is_empty() { "]\n" } else { "\n]\n" });
+    out
+}
+
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (Vec<String>, Vec<Vec<CellValue>>) {
+        let headers = vec!["id".to_string(), "note".to_string()];
+        let rows = vec![
+            vec![CellValue::Integer(1), CellValue::Text("a, \"b\"\nc".to_string())],
+            vec![CellValue::Real(2.5), CellValue::Null],
+            vec![CellValue::Blob(vec![0, 255]), CellValue::Text("NULL".to_string())],
+            vec![CellValue::Integer(4), CellValue::Text(String::new())],
+        ];
+        (headers, rows)
+    }
+
+    #[test]
+    fn csv_quotes_special_fields_and_keeps_null_distinct() {
+        let (headers, rows) = sample();
+        assert_eq!(
+            to_csv(&headers, &rows),
+            "id,note\r\n1,\"a, \"\"b\"\"\nc\"\r\n2.5,\r\n00ff,NULL\r\n4,\"\"\r\n"
+        );
+    }
+
+    #[test]
+    fn json_emits_objects_keyed_by_header() {
+        let (headers, rows) = sample();
+        assert_eq!(
+            to_json(&headers, &rows),
+            "[\n  {\"id\": 1, \"note\": \"a, \\\"b\\\"\\nc\"},\n  {\"id\": 2.5, \"note\": \
+             null},\n  {\"id\": \"00ff\", \"note\": \"NULL\"},\n  {\"id\": 4, \"note\": \"\"}\n]\n"
+        );
+        assert_eq!(to_json(&headers, &[]), "[]\n");
+    }
+}
@@ -0,0 +1,162 @@
+//! User-configurable key bindings, written as specs like `ctrl+h` or `tab`.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::{Config, Value};
+
+/// Config section holding key binding overrides.
+pub const KEYS_SECTION: &str = "keys";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub const fn ctrl(ch: char) -> Self {
+        Self { code: KeyCode::Char(ch), modifiers: KeyModifiers::CONTROL }
+    }
+
+    /// Parses `[ctrl+][alt+][shift+]<key>` where `<key>` is a single character or a named key
+    /// such as `tab`, `left`, or `f5`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = spec.trim().split('+').collect();
+        let key = parts.pop()?.trim();
+        for part in parts {
+            modifiers |= match part.trim().to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            "tab" => KeyCode::Tab,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            lower => {
+                if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    KeyCode::F(n)
+                } else {
+                    let mut chars = key.chars();
+                    let ch = chars.next()?;
+                    if chars.next().is_some() {
+                        return None;
+                    }
+                    // Control chords arrive lowercase regardless of shift state
+                    if modifiers.contains(KeyModifiers::CONTROL) {
+                        KeyCode::Char(ch.to_ascii_lowercase())
+                    } else {
+                        KeyCode::Char(ch)
+                    }
+                }
+            },
+        };
+        Some(Self { code, modifiers })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let code = match key.code {
+            KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(ch.to_ascii_lowercase())
+            },
+            code => code,
+        };
+        // Shift is implied by the character itself for plain keys
+        let modifiers = match code {
+            KeyCode::Char(_) if !self.modifiers.contains(KeyModifiers::SHIFT) => {
+                key.modifiers - KeyModifiers::SHIFT
+            },
+            _ => key.modifiers,
+        };
+        code == self.code && modifiers == self.modifiers
+    }
+}
+
+/// Parses a comma-separated list of key specs, e.g. `"ctrl+h, ctrl+k"`.
+pub fn parse_bindings(specs: &str) -> Option<Vec<KeyBinding>> {
+    specs.split(',').map(KeyBinding::parse).collect()
+}
+
+/// Keys that move focus to a specific pane, independent of the `tab` toggle.
+pub struct FocusKeys {
+    pub editor: Vec<KeyBinding>,
+    pub results: Vec<KeyBinding>,
+}
+
+impl Default for FocusKeys {
+    fn default() -> Self {
+        Self {
+            editor: vec![KeyBinding::ctrl('h'), KeyBinding::ctrl('k')],
+            results: vec![KeyBinding::ctrl('l'), KeyBinding::ctrl('j')],
+        }
+    }
+}
+
+impl FocusKeys {
+    /// Reads `focus_editor` / `focus_results` overrides, returning a warning for each invalid one.
+    pub fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let mut keys = Self::default();
+        let mut warnings = Vec::new();
+        for (name, target) in
+            [("focus_editor", &mut keys.editor), ("focus_results", &mut keys.results)]
+        {
+            let Some(value) = config.get(KEYS_SECTION, name) else {
+                continue;
+            };
+            match value {
+                Value::String(specs) => match parse_bindings(specs) {
+                    Some(bindings) => *target = bindings,
+                    None => warnings.push(format!("invalid key binding {} = {}", name, value)),
+                },
+                _ => warnings.push(format!("{} must be a string like \"ctrl+h\"", name)),
+            }
+        }
+        (keys, warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_matches_key_specs() {
+        let ctrl_h = KeyBinding::parse("Ctrl+H").expect("spec should parse");
+        assert!(ctrl_h.matches(&KeyEvent::new(KeyCode::Char('h'), KeyModifiers::CONTROL)));
+        assert!(!ctrl_h.matches(&KeyEvent::from(KeyCode::Char('h'))));
+
+        let tab = KeyBinding::parse("tab").expect("spec should parse");
+        assert!(tab.matches(&KeyEvent::from(KeyCode::Tab)));
+
+        let upper = KeyBinding::parse("J").expect("spec should parse");
+        assert!(upper.matches(&KeyEvent::new(KeyCode::Char('J'), KeyModifiers::SHIFT)));
+
+        assert_eq!(KeyBinding::parse("f5").map(|k| k.code), Some(KeyCode::F(5)));
+        assert!(KeyBinding::parse("hyper+x").is_none());
+        assert!(KeyBinding::parse("ctrl+ab").is_none());
+    }
+
+    #[test]
+    fn focus_keys_read_overrides_from_config() {
+        let mut config = Config::default();
+        config.set(KEYS_SECTION, "focus_editor", Value::String("alt+1, f1".to_string()));
+        config.set(KEYS_SECTION, "focus_results", Value::String("nope+2".to_string()));
+        let (keys, warnings) = FocusKeys::from_config(&config);
+        assert_eq!(keys.editor.len(), 2);
+        assert!(keys.editor[0].matches(&KeyEvent::new(KeyCode::Char('1'), KeyModifiers::ALT)));
+        assert_eq!(keys.results, FocusKeys::default().results);
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -1,6 +1,8 @@
 use std::{
     env, fs, io,
     path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, MutexGuard},
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result};
@@ -21,9 +23,44 @@ use ratatui::{
     prelude::Widget,
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table, Wrap},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, Tabs, Wrap,
+    },
 };
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+use crate::{
+    config::{Config, Value},
+    export::ExportFormat,
+    keys::FocusKeys,
+    settings::SettingsState,
+    sort::SortSettings,
+    theme::Theme,
+    value::{CellValue, is_boolean_type},
+};
+
+mod config;
+mod diff;
+mod export;
+mod keys;
+mod settings;
+mod sort;
+mod theme;
+mod value;
+
+const MAX_COLUMN_WIDTH: usize = 40;
+/// LIMIT offered for recursive CTEs that have none.
+const RECURSIVE_CTE_ROW_CAP: usize = 1000;
+/// Default `--max-rows`, so an unbounded `SELECT *` can't exhaust memory.
+const DEFAULT_MAX_ROWS: usize = 10_000;
+/// Auto-refresh interval when neither `--refresh-secs` nor `--tail` gives one.
+const DEFAULT_REFRESH_SECS: u64 = 5;
+/// Result sets kept as tabs; the oldest is dropped when a run would open another.
+const MAX_RESULT_TABS: usize = 5;
+/// Larger result sets would turn into unreadably wide transposed tables.
+const TRANSPOSE_MAX_ROWS: usize = 200;
+/// SQLite's name for a private in-memory database.
+const MEMORY_DATABASE: &str = ":memory:";
 
 const SQL_KEYWORDS: &[&str] = &[
     "SELECT",
@@ -130,41 +167,601 @@ struct AutocompleteState {
 }
 
 struct Schema {
+    /// Sorted table names.
     tables: Vec<String>,
-    columns: Vec<String>,
-    columns_by_table: std::collections::HashMap<String, Vec<String>>,
+    /// Columns in table order, keyed by lowercase table name.
+    columns_by_table: std::collections::HashMap<String, Vec<ColumnInfo>>,
+    table_completions: CompletionList,
+    column_completions: CompletionList,
+    column_completions_by_table: std::collections::HashMap<String, CompletionList>,
+}
+
+impl Schema {
+    fn new(
+        mut tables: Vec<String>,
+        columns_by_table: std::collections::HashMap<String, Vec<ColumnInfo>>,
+    ) -> Self {
+        tables.sort();
+        tables.dedup();
+        Self {
+            table_completions: CompletionList::new(tables.iter().cloned()),
+            column_completions: CompletionList::new(
+                columns_by_table.values().flatten().map(|c| c.name.clone()),
+            ),
+            column_completions_by_table: columns_by_table
+                .iter()
+                .map(|(table, columns)| {
+                    (table.clone(), CompletionList::new(columns.iter().map(|c| c.name.clone())))
+                })
+                .collect(),
+            tables,
+            columns_by_table,
+        }
+    }
+}
+
+/// One row of `PRAGMA table_info`.
+#[derive(Clone, Debug, PartialEq)]
+struct ColumnInfo {
+    name: String,
+    /// Declared type; empty when the column was declared without one.
+    decl_type: String,
+    not_null: bool,
+    /// Default expression as written in the `CREATE TABLE`.
+    default: Option<String>,
+    /// Position in the primary key, counting from 1; 0 when not part of it.
+    pk: i64,
+}
+
+/// Sorted, deduplicated completion candidates, so each keystroke only has to filter.
+struct CompletionList {
+    /// `(uppercase, original)` pairs, ordered by original.
+    entries: Vec<(String, String)>,
+}
+
+impl CompletionList {
+    fn new(names: impl IntoIterator<Item = String>) -> Self {
+        let mut names: Vec<String> = names.into_iter().collect();
+        names.sort();
+        names.dedup();
+        Self { entries: names.into_iter().map(|name| (name.to_uppercase(), name)).collect() }
+    }
+
+    fn matching<'a>(&'a self, prefix_upper: &'a str) -> impl Iterator<Item = &'a String> {
+        self.entries
+            .iter()
+            .filter(move |(upper, _)| upper.starts_with(prefix_upper))
+            .map(|(_, name)| name)
+    }
 }
 
-struct TablePickerState {
+static KEYWORD_COMPLETIONS: LazyLock<CompletionList> =
+    LazyLock::new(|| CompletionList::new(SQL_KEYWORDS.iter().map(|&s| s.to_string())));
+
+/// Filterable list popup (table picker, history picker).
+struct PickerState {
     visible: bool,
     filter: String,
     selected: usize,
 }
 
+struct CommandLineState {
+    visible: bool,
+    input: String,
+    /// `:` for commands, `/` while typing a results search.
+    prompt: char,
+}
+
+/// A results search: the lowercased term and the cell the search started from.
+struct SearchState {
+    term: String,
+    origin: (usize, usize),
+}
+
+/// A table or view as recorded in `sqlite_master`.
+struct SchemaObject {
+    kind: String,
+    name: String,
+    sql: Option<String>,
+    columns: Vec<ColumnInfo>,
+}
+
+/// Work waiting on a yes/no answer from the user.
+enum ConfirmAction {
+    /// Statements to run where some contain a recursive CTE with no LIMIT; `y` caps them.
+    RecursiveCte(Vec<String>, BindParams),
+    /// Table to empty with `DELETE FROM`.
+    ClearTable(String),
+}
+
+struct ConfirmState {
+    message: String,
+    /// Key hints shown under the message.
+    options: &'static str,
+    action: ConfirmAction,
+}
+
+/// One run's result set with everything tied to its rows: the cursor, sort, `--compare` output,
+/// and marks. The active tab is the one drawn and navigated.
+#[derive(Default)]
+struct ResultTab {
+    /// Statement whose rows the tab holds, for the tab strip.
+    sql: String,
+    headers: Vec<String>,
+    results: Vec<Vec<CellValue>>,
+    /// Original headers and rows while the transposed view is shown.
+    untransposed: Option<(Vec<String>, Vec<Vec<CellValue>>)>,
+    /// Per column, whether it is declared boolean, from `QueryOutput::boolean_columns`.
+    boolean_columns: Vec<bool>,
+    /// Column the results are sorted by client-side, and whether descending.
+    sort: Option<(usize, bool)>,
+    /// Safety LIMIT squeal added to the query, or the `--max-rows` cap, when the results
+    /// reached it.
+    row_limit: Option<usize>,
+    /// Per result row, whether it is new since the previous `--tail` refresh.
+    changed_rows: Vec<bool>,
+    /// Active results search; matching cells are highlighted while it is set.
+    search: Option<SearchState>,
+    /// The same query's output from the `--compare` database.
+    compare: Option<ComparedResults>,
+    current_row: usize,
+    current_col: usize,
+    vertical_scroll: usize,
+    horizontal_scroll: usize,
+}
+
+impl ResultTab {
+    /// Row and column under the cursor.
+    fn cursor(&self) -> (usize, usize) {
+        (self.current_row, self.current_col)
+    }
+
+    /// `boolean_columns` for the columns on screen: none while transposed, since a column of
+    /// the transposed view mixes every original column.
+    fn boolean_columns(&self) -> &[bool] {
+        if self.untransposed.is_some() { &[] } else { &self.boolean_columns }
+    }
+
+    fn cell_matches_search(&self, cell: &CellValue) -> bool {
+        self.search.as_ref().is_some_and(|s| cell.display().to_lowercase().contains(&s.term))
+    }
+}
+
+/// Second database opened read-only with `--compare`; every read-only query also runs against it.
+struct ComparePane {
+    /// File name shown in the pane title and status.
+    name: String,
+    conn: Arc<Mutex<Connection>>,
+}
+
+/// A query's output from the `--compare` database, matched against the main results.
+struct ComparedResults {
+    headers: Vec<String>,
+    results: Vec<Vec<CellValue>>,
+    boolean_columns: Vec<bool>,
+    /// Per row of `results`, whether the main result set lacks a matching row.
+    unmatched: Vec<bool>,
+    /// Main result rows with no matching row here.
+    missing: usize,
+    error: Option<String>,
+}
+
+impl ComparedResults {
+    fn new(compared: Result<QueryOutput, String>, main: &[Vec<CellValue>]) -> Self {
+        match compared {
+            Ok(QueryOutput { headers, rows: results, boolean_columns, .. }) => Self {
+                unmatched: unmatched_rows(&results, main),
+                missing: unmatched_rows(main, &results).iter().filter(|&&u| u).count(),
+                headers,
+                results,
+                boolean_columns,
+                error: None,
+            },
+            Err(e) => Self {
+                headers: Vec::new(),
+                results: Vec::new(),
+                boolean_columns: Vec::new(),
+                unmatched: Vec::new(),
+                missing: 0,
+                error: Some(e),
+            },
+        }
+    }
+
+    /// Status suffix comparing these results, from the database called `name`, with the main ones.
+    fn summary(&self, name: &str) -> String {
+        match &self.error {
+            Some(e) if e.ends_with(COMPARE_SKIPPED_WRITE) => {
+                return format!(" | {}: not run (query writes data)", name);
+            },
+            Some(_) => return format!(" | {}: query failed", name),
+            None => {},
+        }
+        let extra = self.unmatched.iter().filter(|&&u| u).count();
+        if extra == 0 && self.missing == 0 {
+            format!(" | {}: identical", name)
+        } else {
+            format!(
+                " | {}: {} rows, {} only in main, {} only in {}",
+                name,
+                format_count(self.results.len()),
+                format_count(self.missing),
+                format_count(extra),
+                name
+            )
+        }
+    }
+}
+
+/// Why a query wasn't sent to the `--compare` database, which is never written to.
+const COMPARE_SKIPPED_WRITE: &str = "only read-only statements are sent to the compare database";
+
+/// Bind parameter values keyed like `parameter_key`: `:name`, or `?N` for numbered and bare
+/// placeholders.
+type BindParams = Vec<(String, rusqlite::types::Value)>;
+
+/// Values being collected for a query's placeholders before it runs.
+struct BindPrompt {
+    statements: Vec<String>,
+    /// Parameter keys still to fill, in binding order.
+    names: Vec<String>,
+    values: BindParams,
+    input: String,
+}
+
+/// Statements running on the blocking pool while `run_app` keeps drawing; `esc` interrupts them.
+struct RunningQuery {
+    task: tokio::task::JoinHandle<Result<RunOutcome>>,
+    /// For the session connection and, with `--compare`, the other database.
+    interrupts: Vec<rusqlite::InterruptHandle>,
+    cancelled: bool,
+    log: tokio::sync::mpsc::UnboundedReceiver<String>,
+    started: Instant,
+    total: usize,
+    follow_up: FollowUp,
+}
+
+/// Output of the session run and, with `--compare`, of the other database.
+type RunOutcome = (QueryOutput, Option<Result<QueryOutput, String>>);
+
+/// What `finish_query` does beyond showing the results.
+enum FollowUp {
+    None,
+    /// Recursive CTEs were capped at `RECURSIVE_CTE_ROW_CAP`; say so if the results reach it.
+    RecursiveCap,
+    /// A `--tail` refresh: keep the cursor and mark rows new since the previous results.
+    Tail {
+        had_results: bool,
+    },
+}
+
+/// What the last statement of a run produced.
+struct QueryOutput {
+    headers: Vec<String>,
+    rows: Vec<Vec<CellValue>>,
+    /// Per column, whether its declared type is boolean (`is_boolean_type`); expressions have
+    /// no declared type.
+    boolean_columns: Vec<bool>,
+    /// Rows changed, when the statement returns no columns (INSERT, UPDATE, DDL, ...).
+    changes: Option<u64>,
+    /// Fetching stopped at `--max-rows` with rows still to come.
+    truncated: bool,
+}
+
+/// Read-only, scrollable text shown in a centered modal.
+struct TextPopup {
+    title: String,
+    lines: Vec<String>,
+    scroll: usize,
+    /// Colour lines starting with `+ ` / `- ` as added / removed.
+    diff: bool,
+    /// Wrap long lines; tabular popups turn this off and scroll sideways instead.
+    wrap: bool,
+    hscroll: usize,
+}
+
+impl TextPopup {
+    fn new(title: impl Into<String>, lines: Vec<String>) -> Self {
+        Self { title: title.into(), lines, scroll: 0, diff: false, wrap: true, hscroll: 0 }
+    }
+}
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(value_name = "DATABASE")]
-    database: String,
+    /// Database to open; any further paths are attached under aliases taken from their file
+    /// names
+    #[arg(value_name = "DATABASE", required = true, num_args = 1..)]
+    database: Vec<String>,
+
+    /// How long to wait for a locked database before failing with SQLITE_BUSY
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    busy_timeout: u64,
+
+    /// Also run every query against this database and show both result sets side by side
+    #[arg(long, value_name = "PATH")]
+    compare: Option<String>,
+
+    /// Re-run the last read-only query every SECONDS and highlight rows that changed
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    tail: Option<u64>,
+
+    /// Interval for the auto-refresh toggled with R (default: the --tail interval, else 5)
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    refresh_secs: Option<u64>,
+
+    /// Start the editor in Normal mode instead of Insert mode
+    #[arg(long)]
+    normal: bool,
+
+    /// Open every database read-only so no statement can change it
+    #[arg(long)]
+    read_only: bool,
+
+    /// Stop fetching a query's rows after this many (0 fetches them all)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_ROWS)]
+    max_rows: usize,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Pane {
     Editor,
     Results,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SplitOrientation {
+    /// Editor above results.
+    Vertical,
+    /// Editor left of results.
+    Horizontal,
+}
+
+/// Autocomplete popup size caps, read from `[autocomplete]` in the config file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct AutocompleteLimits {
+    max_height: u16,
+    /// Unset by default, so long suggestions are only cut off by the editor edge.
+    max_width: u16,
+}
+
+impl Default for AutocompleteLimits {
+    fn default() -> Self {
+        Self { max_height: 8, max_width: u16::MAX }
+    }
+}
+
+impl AutocompleteLimits {
+    const SECTION: &str = "autocomplete";
+    /// Narrowest popup, unless `max_width` is smaller.
+    const MIN_WIDTH: u16 = 20;
+
+    fn from_config(config: &Config) -> Self {
+        let defaults = Self::default();
+        let size = |key, default: u16| {
+            config
+                .get(Self::SECTION, key)
+                .and_then(Value::as_integer)
+                .map_or(default, |n| n.clamp(1, 200) as u16)
+        };
+        Self {
+            max_height: size("max_height", defaults.max_height),
+            max_width: size("max_width", defaults.max_width),
+        }
+    }
+
+    /// Popup size for `suggestions`: wide enough for the longest one and tall enough for all of
+    /// them, within the caps.
+    fn popup_size(&self, suggestions: &[String]) -> (u16, u16) {
+        let longest = suggestions.iter().map(|s| s.chars().count()).max().unwrap_or(0);
+        let width =
+            (longest.min(u16::MAX as usize) as u16).max(Self::MIN_WIDTH).min(self.max_width);
+        let height = suggestions.len().min(self.max_height as usize) as u16;
+        (width, height)
+    }
+}
+
+/// How completed keywords are cased, set by `[autocomplete] keyword_case`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum KeywordCase {
+    /// Follow the typed prefix: `sel` -> `select`, `Sel` -> `Select`, anything else upper.
+    #[default]
+    Match,
+    Upper,
+    Lower,
+}
+
+impl KeywordCase {
+    /// Reads `keyword_case = "match"|"upper"|"lower"`, returning a warning for an invalid value.
+    fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let Some(value) = config.get(AutocompleteLimits::SECTION, "keyword_case") else {
+            return (Self::default(), Vec::new());
+        };
+        match value.as_str().map(str::to_ascii_lowercase).as_deref() {
+            Some("match") => (Self::Match, Vec::new()),
+            Some("upper") => (Self::Upper, Vec::new()),
+            Some("lower") => (Self::Lower, Vec::new()),
+            _ => (
+                Self::default(),
+                vec![format!(
+                    "[autocomplete] keyword_case must be \"match\", \"upper\", or \"lower\", got \
+                     {}",
+                    value
+                )],
+            ),
+        }
+    }
+
+    /// Cases `keyword` (canonically upper) for insertion after `typed`. A lone capital could be
+    /// the start of either `SELECT` or `Select`, so it keeps the canonical case.
+    fn apply(self, keyword: &str, typed: &str) -> String {
+        let mut letters = typed.chars().filter(|c| c.is_alphabetic());
+        let lower = || keyword.to_lowercase();
+        match self {
+            Self::Upper => keyword.to_string(),
+            Self::Lower => lower(),
+            Self::Match => match letters.next() {
+                Some(first) if first.is_lowercase() && letters.all(char::is_lowercase) => lower(),
+                Some(first)
+                    if first.is_uppercase()
+                        && letters.clone().next().is_some()
+                        && letters.all(char::is_lowercase) =>
+                {
+                    // Capitalize each word, so `Gro` completes to `Group By`
+                    let mut capitalized = String::with_capacity(keyword.len());
+                    let mut word_start = true;
+                    for c in keyword.chars() {
+                        if word_start {
+                            capitalized.extend(c.to_uppercase());
+                        } else {
+                            capitalized.extend(c.to_lowercase());
+                        }
+                        word_start = !c.is_alphanumeric() && c != '_';
+                    }
+                    capitalized
+                },
+                _ => keyword.to_string(),
+            },
+        }
+    }
+}
+
+/// Results table styling, persisted under `[results]` in the config file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TableStyle {
+    /// Alternate row colours.
+    zebra: bool,
+    /// Draw a thin line between rows.
+    row_separators: bool,
+}
+
+impl TableStyle {
+    const SECTION: &str = "results";
+
+    fn from_config(config: &Config) -> Self {
+        let flag = |key, default| {
+            config.get(Self::SECTION, key).and_then(Value::as_bool).unwrap_or(default)
+        };
+        Self { zebra: flag("zebra", true), row_separators: flag("row_separators", false) }
+    }
+
+    fn write_to(&self, config: &mut Config) {
+        config.set(Self::SECTION, "zebra", Value::Boolean(self.zebra));
+        config.set(Self::SECTION, "row_separators", Value::Boolean(self.row_separators));
+    }
+}
+
+/// Pane geometry, persisted under `[layout]` in the config file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct LayoutSettings {
+    orientation: SplitOrientation,
+    /// Editor rows (including borders) in the vertical split.
+    editor_height: u16,
+    /// Editor share of the width, in percent, in the horizontal split.
+    editor_width_percent: u16,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            orientation: SplitOrientation::Vertical,
+            editor_height: 10,
+            editor_width_percent: 40,
+        }
+    }
+}
+
+impl LayoutSettings {
+    const SECTION: &str = "layout";
+    const HEIGHT_RANGE: (u16, u16) = (3, 60);
+    const WIDTH_PERCENT_RANGE: (u16, u16) = (15, 85);
+
+    fn from_config(config: &Config) -> Self {
+        let mut layout = Self::default();
+        if let Some(orientation) = config.get(Self::SECTION, "orientation").and_then(Value::as_str)
+        {
+            layout.orientation = match orientation {
+                "horizontal" => SplitOrientation::Horizontal,
+                _ => SplitOrientation::Vertical,
+            };
+        }
+        let clamp = |value: i64, (min, max): (u16, u16)| value.clamp(min.into(), max.into()) as u16;
+        if let Some(height) = config.get(Self::SECTION, "editor_height").and_then(Value::as_integer)
+        {
+            layout.editor_height = clamp(height, Self::HEIGHT_RANGE);
+        }
+        if let Some(width) =
+            config.get(Self::SECTION, "editor_width_percent").and_then(Value::as_integer)
+        {
+            layout.editor_width_percent = clamp(width, Self::WIDTH_PERCENT_RANGE);
+        }
+        layout
+    }
+
+    fn write_to(&self, config: &mut Config) {
+        let orientation = match self.orientation {
+            SplitOrientation::Vertical => "vertical",
+            SplitOrientation::Horizontal => "horizontal",
+        };
+        config.set(Self::SECTION, "orientation", Value::String(orientation.to_string()));
+        config.set(Self::SECTION, "editor_height", Value::Integer(self.editor_height.into()));
+        config.set(
+            Self::SECTION,
+            "editor_width_percent",
+            Value::Integer(self.editor_width_percent.into()),
+        );
+    }
+
+    /// Grows (positive `delta`) or shrinks the editor along the current split direction.
+    fn resize_editor(&mut self, delta: i16) {
+        let (value, (min, max), step) = match self.orientation {
+            SplitOrientation::Vertical => (&mut self.editor_height, Self::HEIGHT_RANGE, 1),
+            SplitOrientation::Horizontal => {
+                (&mut self.editor_width_percent, Self::WIDTH_PERCENT_RANGE, 5)
+            },
+        };
+        *value = value.saturating_add_signed(delta * step).clamp(min, max);
+    }
+}
+
 struct App {
     editor_state: EditorState,
     event_handler: EditorEventHandler,
     database_path: String,
-    results: Vec<Vec<String>>,
-    headers: Vec<String>,
+    conn: Arc<Mutex<Connection>>,
+    /// Connection to the real database while a sandbox copy is active.
+    sandbox_origin: Option<Arc<Mutex<Connection>>>,
+    compare: Option<ComparePane>,
+    /// One tab per run that returned columns, oldest first; never empty.
+    result_tabs: Vec<ResultTab>,
+    active_tab: usize,
+    /// Show the current row as a vertical list of `header  value` pairs instead of the table.
+    expanded_row: bool,
+    sort_settings: SortSettings,
+    /// Most rows a query fetches (`--max-rows`); 0 fetches everything.
+    max_rows: usize,
+    /// One line per statement of the last run, shown by `:log`.
+    script_log: Vec<String>,
+    /// Statements behind the current results, re-run by `--tail`.
+    last_statements: Option<Vec<String>>,
+    /// Bind parameter values the last statements ran with.
+    last_params: BindParams,
+    bind_prompt: Option<BindPrompt>,
+    running: Option<RunningQuery>,
+    /// File last read with `:e` or written with `:w`; a bare `:w` writes it again.
+    query_file: Option<PathBuf>,
+    /// Editor text as last read with `:e` or written with `:w`, to tell whether `:e` would lose
+    /// edits.
+    saved_query: Option<String>,
+    /// Leave queries running while the UI carries on (see `run_statements`); tests wait instead.
+    queries_in_background: bool,
+    tail_interval: Option<Duration>,
+    /// What `R` sets `tail_interval` to.
+    refresh_period: Duration,
+    /// Editor text when `R` started auto-refresh; editing it pauses the refresh. Unset for
+    /// `--tail`.
+    refresh_query: Option<String>,
     status: String,
-    current_row: usize,
-    current_col: usize,
-    vertical_scroll: usize,
-    horizontal_scroll: usize,
     visible_rows: usize,
     visible_cols: usize,
     autocomplete: AutocompleteState,
@@ -173,34 +770,117 @@ struct App {
     query_history: Vec<String>,
     history_index: Option<usize>,
     history_draft: Option<String>,
-    history_path: PathBuf,
-    table_picker: TablePickerState,
+    /// `None` for `:memory:`, whose queries have nothing to be remembered against.
+    history_path: Option<PathBuf>,
+    table_picker: PickerState,
+    history_picker: PickerState,
+    command_line: CommandLineState,
+    editor_collapsed: bool,
+    layout: LayoutSettings,
+    table_style: TableStyle,
+    autocomplete_limits: AutocompleteLimits,
+    popup: Option<TextPopup>,
+    /// Opened on first copy; kept alive because X11/Wayland clipboards are served by the owner.
+    clipboard: Option<arboard::Clipboard>,
+    config: Config,
+    config_path: PathBuf,
+    /// The config file couldn't be read at startup, so saving would replace it with defaults.
+    config_load_failed: bool,
+    settings: Option<SettingsState>,
+    confirm: Option<ConfirmState>,
+    focus_keys: FocusKeys,
+    /// A `g` was pressed in normal mode and we're waiting to see if it starts `gd`.
+    pending_g: bool,
+    /// Whether generated and history-loaded queries keep their trailing `;`.
+    trailing_semicolon: bool,
+    keyword_case: KeywordCase,
+    /// Started with `--read-only`; every connection to a file was opened without write access.
+    read_only: bool,
+    /// Extra databases from the command line, attached to every connection squeal opens.
+    attachments: Vec<Attachment>,
+    /// Colours and syntax theme resolved from `[theme]` at startup.
+    theme: Theme,
 }
 
 impl App {
-    fn new(database: &str) -> Result<Self> {
-        let conn = Connection::open(database).context("Failed to open database")?;
+    fn new(cli: &Cli) -> Result<Self> {
+        let database = cli.database[0].as_str();
+        let attachments = attachments_for(&cli.database[1..]);
+        let conn = open_database(database, cli.busy_timeout, cli.read_only, &attachments)?;
+
+        let mut warnings = Vec::new();
+        let config_path = history_root_dir()?.join("config.toml");
+        let mut config_load_failed = false;
+        let config = Config::load(&config_path).unwrap_or_else(|e| {
+            warnings.push(format!("ignoring invalid config: {}", e));
+            config_load_failed = true;
+            Config::default()
+        });
 
         let mut editor_state = EditorState::default();
-        editor_state.mode = EditorMode::Insert;
+        editor_state.mode = start_mode(cli.normal, &config);
         let event_handler = EditorEventHandler::default();
+        warnings.extend(settings::apply_saved(&conn, &config));
+        let compare = match &cli.compare {
+            Some(path) => {
+                // Read-only, so a mistyped path fails instead of creating an empty database
+                let other = open_database(path, cli.busy_timeout, true, &attachments)?;
+                settings::apply_saved(&other, &config);
+                Some(ComparePane {
+                    name: Path::new(path)
+                        .file_name()
+                        .map_or_else(|| path.clone(), |n| n.to_string_lossy().to_string()),
+                    conn: Arc::new(Mutex::new(other)),
+                })
+            },
+            None => None,
+        };
+        let (focus_keys, key_warnings) = FocusKeys::from_config(&config);
+        let layout = LayoutSettings::from_config(&config);
+        let trailing_semicolon =
+            config.get("editor", "trailing_semicolon").and_then(Value::as_bool).unwrap_or(true);
+        warnings.extend(key_warnings);
+        let (sort_settings, sort_warnings) = SortSettings::from_config(&config);
+        warnings.extend(sort_warnings);
+        let (theme, theme_warnings) = Theme::from_config(&config);
+        warnings.extend(theme_warnings);
+        let (keyword_case, case_warnings) = KeywordCase::from_config(&config);
+        warnings.extend(case_warnings);
 
         let schema = Self::load_schema(&conn)?;
         let resolved_database_path = resolve_database_path(database)?;
         let history_path = history_file_path_for_database(&resolved_database_path)?;
-        let query_history = load_query_history(&history_path)?;
+        let query_history = match &history_path {
+            Some(path) => load_query_history(path)?,
+            None => Vec::new(),
+        };
 
         let mut app = Self {
             editor_state,
             event_handler,
             database_path: resolved_database_path.to_string_lossy().to_string(),
-            results: Vec::new(),
-            headers: Vec::new(),
+            conn: Arc::new(Mutex::new(conn)),
+            sandbox_origin: None,
+            compare,
+            expanded_row: false,
+            sort_settings,
+            result_tabs: vec![ResultTab::default()],
+            active_tab: 0,
+            max_rows: cli.max_rows,
+            script_log: Vec::new(),
+            last_statements: None,
+            last_params: Vec::new(),
+            bind_prompt: None,
+            running: None,
+            query_file: None,
+            saved_query: None,
+            queries_in_background: true,
+            tail_interval: cli.tail.map(Duration::from_secs),
+            refresh_period: Duration::from_secs(
+                cli.refresh_secs.or(cli.tail).unwrap_or(DEFAULT_REFRESH_SECS),
+            ),
+            refresh_query: None,
             status: String::from("ready"),
-            current_row: 0,
-            current_col: 0,
-            vertical_scroll: 0,
-            horizontal_scroll: 0,
             visible_rows: 10,
             visible_cols: 5,
             autocomplete: AutocompleteState {
@@ -214,51 +894,80 @@ impl App {
             history_index: None,
             history_draft: None,
             history_path,
-            table_picker: TablePickerState { visible: false, filter: String::new(), selected: 0 },
+            table_picker: PickerState { visible: false, filter: String::new(), selected: 0 },
+            history_picker: PickerState { visible: false, filter: String::new(), selected: 0 },
+            command_line: CommandLineState { visible: false, input: String::new(), prompt: ':' },
+            editor_collapsed: false,
+            layout,
+            table_style: TableStyle::from_config(&config),
+            autocomplete_limits: AutocompleteLimits::from_config(&config),
+            popup: None,
+            clipboard: None,
+            config,
+            config_path,
+            config_load_failed,
+            settings: None,
+            confirm: None,
+            focus_keys,
+            pending_g: false,
+            trailing_semicolon,
+            keyword_case,
+            read_only: cli.read_only,
+            attachments,
+            theme,
         };
 
         if let Some(last_query) = app.query_history.last().cloned() {
-            app.set_query(&last_query);
+            app.set_loaded_query(&last_query);
             app.status = String::from("Loaded latest query from history");
         }
+        if !warnings.is_empty() {
+            app.status = format!("Warning: {}", warnings.join("; "));
+        }
 
         Ok(app)
     }
 
+    /// Tables of every attached database. Tables outside `main` are qualified with their schema
+    /// (`aux1.orders`) and their columns are also reachable by the bare table name unless `main`
+    /// has a table of that name.
     fn load_schema(conn: &Connection) -> Result<Schema> {
         let mut tables = Vec::new();
-        let mut columns = Vec::new();
-        let mut columns_by_table = std::collections::HashMap::<String, Vec<String>>::new();
-
-        let mut stmt = conn
-            .prepare("SELECT name FROM sqlite_master WHERE type='table'")
-            .context("Failed to query tables")?;
-        let table_names: Vec<String> = stmt
-            .query_map([], |row| row.get(0))
-            .context("Failed to fetch tables")?
-            .filter_map(Result::ok)
-            .collect();
-
-        for table in &table_names {
-            tables.push(table.clone());
+        let mut columns_by_table = std::collections::HashMap::<String, Vec<ColumnInfo>>::new();
+
+        let schemas = database_names(conn)?;
+        let mut unqualified = Vec::new();
+        for schema in &schemas {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT name FROM {}.sqlite_master WHERE type='table'",
+                    quote_identifier(schema)
+                ))
+                .context("Failed to query tables")?;
+            let table_names: Vec<String> = stmt
+                .query_map([], |row| row.get(0))
+                .context("Failed to fetch tables")?
+                .filter_map(Result::ok)
+                .collect();
 
-            if let Ok(mut col_stmt) = conn.prepare(&format!("PRAGMA table_info({})", table)) {
-                let table_columns: Vec<String> =
-                    match col_stmt.query_map([], |row| row.get::<_, String>(1)) {
-                        Ok(rows) => rows.filter_map(Result::ok).collect(),
-                        Err(_) => Vec::new(),
-                    };
-                columns.extend(table_columns.iter().cloned());
-                columns_by_table.insert(table.to_lowercase(), table_columns);
+            for table in &table_names {
+                let columns = table_columns(conn, schema, table);
+                if schema == "main" {
+                    tables.push(table.clone());
+                    columns_by_table.insert(table.to_lowercase(), columns);
+                } else {
+                    let qualified = format!("{}.{}", schema, table);
+                    tables.push(qualified.clone());
+                    columns_by_table.insert(qualified.to_lowercase(), columns.clone());
+                    unqualified.push((table.to_lowercase(), columns));
+                }
             }
         }
+        for (table, columns) in unqualified {
+            columns_by_table.entry(table).or_insert(columns);
+        }
 
-        tables.sort();
-        tables.dedup();
-        columns.sort();
-        columns.dedup();
-
-        Ok(Schema { tables, columns, columns_by_table })
+        Ok(Schema::new(tables, columns_by_table))
     }
 
     fn update_autocomplete(&mut self) {
@@ -280,15 +989,11 @@ impl App {
         let current_line = text.lines().nth(line).unwrap_or("");
         let before_cursor = prefix_at_char(current_line, col);
 
-        let word_start = before_cursor
-            .rfind(|c: char| !c.is_alphanumeric() && c != '_')
-            .map(|i| i + 1)
-            .unwrap_or(0);
+        let word_start = identifier_start(before_cursor);
         let current_word = &before_cursor[word_start..];
 
         let before_text = text_before_cursor(&text, line, before_cursor);
-        let statement_before =
-            before_text.rsplit_once(';').map(|(_, s)| s).unwrap_or(before_text.as_str());
+        let statement_before = &before_text[last_statement_start(&before_text)..];
         let kind = completion_kind(statement_before);
         let qualifier = qualifier_before_word(before_cursor, word_start);
 
@@ -304,31 +1009,52 @@ impl App {
         }
 
         let prefix_upper = current_word.to_uppercase();
-        let mut suggestions = Vec::<String>::new();
-
-        match kind {
-            CompletionKind::Table => {
-                suggestions.extend(self.schema.tables.iter().cloned());
+        let by_table = &self.schema.column_completions_by_table;
+        let suggestions: Vec<String> = match (kind, &qualifier) {
+            // `aux1.or` completes the tables of the `aux1` schema, without repeating the alias
+            (CompletionKind::Table, Some(q)) => self
+                .schema
+                .table_completions
+                .matching(&format!("{}.{}", q.to_uppercase(), prefix_upper))
+                .map(|table| table.chars().skip(q.chars().count() + 1).collect())
+                .collect(),
+            (CompletionKind::Table, None) => {
+                self.schema.table_completions.matching(&prefix_upper).cloned().collect()
             },
-            CompletionKind::Column => {
-                if let Some(q) = qualifier
-                    && let Some(cols) = self.schema.columns_by_table.get(&q.to_lowercase())
-                {
-                    suggestions.extend(cols.iter().cloned());
+            (CompletionKind::Column, _) => {
+                // The whole statement, since `FROM` usually comes after the cursor
+                let tables = statement_at(&text, before_text.len())
+                    .map(|statement| referenced_tables(&statement))
+                    .unwrap_or_default();
+                let lists: Vec<&CompletionList> = match &qualifier {
+                    Some(q) => {
+                        let table = tables
+                            .iter()
+                            .find(|t| t.alias.as_ref().is_some_and(|a| a.eq_ignore_ascii_case(q)))
+                            .map_or(q.as_str(), |t| t.name.as_str());
+                        by_table.get(&table.to_lowercase()).into_iter().collect()
+                    },
+                    None => {
+                        tables.iter().filter_map(|t| by_table.get(&t.name.to_lowercase())).collect()
+                    },
+                };
+                if lists.is_empty() {
+                    self.schema.column_completions.matching(&prefix_upper).cloned().collect()
                 } else {
-                    suggestions.extend(self.schema.columns.iter().cloned());
+                    let mut columns: Vec<String> = lists
+                        .iter()
+                        .flat_map(|list| list.matching(&prefix_upper).cloned())
+                        .collect();
+                    columns.sort();
+                    columns.dedup();
+                    columns
                 }
             },
-            CompletionKind::Keyword => {
-                suggestions.extend(SQL_KEYWORDS.iter().map(|&s| s.to_string()));
-            },
-        }
-
-        if !prefix_upper.is_empty() {
-            suggestions.retain(|s| s.to_uppercase().starts_with(&prefix_upper));
-        }
-        suggestions.sort();
-        suggestions.dedup();
+            (CompletionKind::Keyword, _) => KEYWORD_COMPLETIONS
+                .matching(&prefix_upper)
+                .map(|keyword| self.keyword_case.apply(keyword, current_word))
+                .collect(),
+        };
 
         if suggestions.is_empty() {
             self.autocomplete.visible = false;
@@ -352,6 +1078,16 @@ impl App {
         self.editor_state.cursor.col = last_col;
     }
 
+    /// `set_query` for text squeal supplies (history, generated queries), honouring
+    /// `trailing_semicolon`.
+    fn set_loaded_query(&mut self, query: &str) {
+        if self.trailing_semicolon {
+            self.set_query(query);
+        } else {
+            self.set_query(strip_trailing_semicolons(query));
+        }
+    }
+
     fn history_len(&self) -> usize {
         self.query_history.len() + usize::from(self.history_draft.is_some())
     }
@@ -391,7 +1127,7 @@ impl App {
         };
         self.history_index = Some(next_index);
         if let Some(entry) = self.history_entry(next_index).map(ToString::to_string) {
-            self.set_query(&entry);
+            self.set_loaded_query(&entry);
         }
     }
 
@@ -418,7 +1154,7 @@ impl App {
         let next_index = index + 1;
         self.history_index = Some(next_index);
         if let Some(entry) = self.history_entry(next_index).map(ToString::to_string) {
-            self.set_query(&entry);
+            self.set_loaded_query(&entry);
         }
     }
 
@@ -426,13 +1162,20 @@ impl App {
         if query.trim().is_empty() {
             return;
         }
-        if self.query_history.last().is_some_and(|last| last == query) {
+        // A stripped history entry run again is still the same query
+        if self
+            .query_history
+            .last()
+            .is_some_and(|last| strip_trailing_semicolons(last) == strip_trailing_semicolons(query))
+        {
             return;
         }
         self.query_history.push(query.to_string());
         self.history_index = None;
         self.history_draft = None;
-        if let Err(e) = save_query_history(&self.history_path, &self.query_history) {
+        if let Some(path) = &self.history_path
+            && let Err(e) = save_query_history(path, &self.query_history)
+        {
             self.status = format!("Warning: failed to save history: {}", e);
         }
     }
@@ -511,9 +1254,13 @@ impl App {
         let table = tables[idx].clone();
         let columns =
             self.schema.columns_by_table.get(&table.to_lowercase()).cloned().unwrap_or_default();
-        let select_clause = if columns.is_empty() { "*".to_string() } else { columns.join(", ") };
+        let select_clause = if columns.is_empty() {
+            "*".to_string()
+        } else {
+            columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+        };
         let query = format!("select {} from {} limit 100;", select_clause, table);
-        self.set_query(&query);
+        self.set_loaded_query(&query);
         self.close_table_picker();
         self.status = format!("Loaded table query: {}", table);
         true
@@ -525,6 +1272,13 @@ impl App {
             KeyCode::Enter => {
                 return self.table_picker_apply_selection();
             },
+            KeyCode::Tab => {
+                let tables = self.filtered_tables();
+                if let Some(table) = tables.get(self.table_picker.selected).cloned() {
+                    self.close_table_picker();
+                    self.describe_table(&table);
+                }
+            },
             KeyCode::Up => self.table_picker_move_up(),
             KeyCode::Down => self.table_picker_move_down(),
             KeyCode::Backspace => self.table_picker_pop_filter(),
@@ -539,410 +1293,3108 @@ impl App {
         false
     }
 
-    fn accept_autocomplete(&mut self) {
-        if !matches!(self.editor_state.mode, EditorMode::Insert) {
-            self.autocomplete.visible = false;
-            return;
-        }
+    /// Indices into `query_history` matching the picker filter, most recent first.
+    fn filtered_history(&self) -> Vec<usize> {
+        let filter = self.history_picker.filter.to_lowercase();
+        (0..self.query_history.len())
+            .rev()
+            .filter(|&i| {
+                filter.is_empty() || self.query_history[i].to_lowercase().contains(&filter)
+            })
+            .collect()
+    }
 
-        if !self.autocomplete.visible || self.autocomplete.suggestions.is_empty() {
+    fn open_history_picker(&mut self) {
+        if self.query_history.is_empty() {
+            self.status = String::from("History is empty");
             return;
         }
+        self.history_picker = PickerState { visible: true, filter: String::new(), selected: 0 };
+        self.status = String::from("History: type to filter, Enter to load");
+    }
 
-        let selected = self.autocomplete.selected.min(self.autocomplete.suggestions.len() - 1);
-        let suggestion = &self.autocomplete.suggestions[selected];
-
-        let cursor = &self.editor_state.cursor;
-        let line = cursor.row;
-        let col = cursor.col;
+    fn handle_history_picker_key(&mut self, key: crossterm::event::KeyEvent) {
+        let picker = &mut self.history_picker;
+        match key.code {
+            KeyCode::Esc => picker.visible = false,
+            KeyCode::Enter => {
+                let Some(&idx) = self.filtered_history().get(self.history_picker.selected) else {
+                    return;
+                };
+                self.history_picker.visible = false;
+                // Continue stepping with h/l from the picked entry
+                self.ensure_history_draft();
+                self.history_index = Some(idx);
+                let entry = self.query_history[idx].clone();
+                self.set_loaded_query(&entry);
+                self.status = format!("Loaded history entry {}", self.query_history.len() - idx);
+            },
+            KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+            KeyCode::Down => {
+                let len = self.filtered_history().len();
+                self.history_picker.selected =
+                    (self.history_picker.selected + 1).min(len.saturating_sub(1));
+            },
+            KeyCode::Backspace => {
+                picker.filter.pop();
+                picker.selected = 0;
+            },
+            KeyCode::Char(ch)
+                if !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && !key.modifiers.contains(KeyModifiers::ALT) =>
+            {
+                picker.filter.push(ch);
+                picker.selected = 0;
+            },
+            _ => {},
+        }
+    }
 
-        let text = self.editor_state.lines.to_string();
-        if line >= text.lines().count() {
-            return;
+    /// Shrinks the editor to a single line so the results table gets the whole screen.
+    fn toggle_editor_collapsed(&mut self) {
+        self.editor_collapsed = !self.editor_collapsed;
+        if self.editor_collapsed {
+            self.focus = Pane::Results;
+            self.status = String::from("Editor collapsed (z to restore)");
+        } else {
+            self.focus = Pane::Editor;
+            self.status = String::from("Editor restored");
         }
+    }
 
-        let current_line = text.lines().nth(line).unwrap_or("");
-        let before_cursor = prefix_at_char(current_line, col);
-        let word_start = before_cursor
-            .rfind(|c: char| !c.is_alphanumeric() && c != '_')
-            .map(|i| i + 1)
-            .unwrap_or(0);
-        let current_word = &before_cursor[word_start..];
-        let current_word_chars = current_word.chars().count();
+    /// The statement the editor cursor is in, for commands that act on one statement of a script.
+    fn statement_under_cursor(&self) -> Option<String> {
+        let cursor = &self.editor_state.cursor;
+        let text = self.current_query();
+        let line = text.lines().nth(cursor.row).unwrap_or("");
+        let before = text_before_cursor(&text, cursor.row, prefix_at_char(line, cursor.col));
+        statement_at(&text, before.len())
+    }
 
-        for _ in 0..current_word_chars {
-            use crossterm::event::KeyEvent;
-            self.event_handler
-                .on_key_event(KeyEvent::from(KeyCode::Backspace), &mut self.editor_state);
+    /// `Ln 3, Col 7 · stmt 2/4` for the editor's bottom border, recomputed every frame.
+    fn cursor_position_label(&self) -> String {
+        let cursor = &self.editor_state.cursor;
+        let mut label = format!("Ln {}, Col {}", cursor.row + 1, cursor.col + 1);
+        let text = self.current_query();
+        let line = text.lines().nth(cursor.row).unwrap_or("");
+        let before = text_before_cursor(&text, cursor.row, prefix_at_char(line, cursor.col));
+        if let Some((index, count)) = statement_index_at(&text, before.len()) {
+            label.push_str(&format!(" · stmt {}/{}", index + 1, count));
         }
+        label
+    }
 
-        for ch in suggestion.chars() {
-            use crossterm::event::KeyEvent;
-            if ch == ' ' {
-                self.event_handler
-                    .on_key_event(KeyEvent::from(KeyCode::Char(' ')), &mut self.editor_state);
-            } else {
-                self.event_handler
-                    .on_key_event(KeyEvent::from(KeyCode::Char(ch)), &mut self.editor_state);
-            }
-        }
+    fn identifier_under_cursor(&self) -> Option<String> {
+        let cursor = &self.editor_state.cursor;
+        let text = self.current_query();
+        let line = text.lines().nth(cursor.row)?;
+        word_at_char(line, cursor.col).map(ToString::to_string)
+    }
 
-        self.autocomplete.visible = false;
+    /// The possibly schema-qualified name under the cursor, split into qualifier and name.
+    fn qualified_name_under_cursor(&self) -> Option<(Option<String>, String)> {
+        let cursor = &self.editor_state.cursor;
+        let text = self.current_query();
+        let line = text.lines().nth(cursor.row)?;
+        let (qualifier, name) = qualified_name_at_char(line, cursor.col)?;
+        Some((qualifier.map(ToString::to_string), name.to_string()))
     }
 
-    async fn execute_query(&mut self) -> Result<()> {
-        let sql = self.editor_state.lines.to_string();
-        if sql.trim().is_empty() {
-            self.status = String::from("Empty query");
+    /// Shows the `CREATE` statement and columns of the table or view named under the cursor. A
+    /// schema-qualified name (`aux1.orders`) is looked up in that database; a bare one in `main`
+    /// first, then the attached databases, as completion resolves it.
+    async fn open_definition_under_cursor(&mut self) -> Result<()> {
+        let Some((qualifier, name)) = self.qualified_name_under_cursor() else {
+            self.status = String::from("No identifier under cursor");
             return Ok(());
-        }
-        self.append_run_query_to_history(&sql);
+        };
+        let typed = match &qualifier {
+            Some(schema) => format!("{}.{}", schema, name),
+            None => name.clone(),
+        };
 
-        let statements: Vec<String> =
-            sql.split(';').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
-        if statements.is_empty() {
-            self.status = String::from("Empty query");
+        let conn = Arc::clone(&self.conn);
+        let definition = tokio::task::spawn_blocking(move || -> Result<Option<SchemaObject>> {
+            let conn = lock_connection(&conn)?;
+            let mut schemas = database_names(&conn)?;
+            // A qualifier that isn't a database (e.g. a table alias) searches them all
+            if let Some(qualifier) = &qualifier
+                && schemas.iter().any(|s| s.eq_ignore_ascii_case(qualifier))
+            {
+                schemas.retain(|s| s.eq_ignore_ascii_case(qualifier));
+            }
+            for schema in schemas {
+                let mut stmt = conn
+                    .prepare(&format!(
+                        "SELECT type, name, sql FROM {}.sqlite_master WHERE type IN ('table', \
+                         'view') AND name = ?1 COLLATE NOCASE",
+                        quote_identifier(&schema)
+                    ))
+                    .context("Failed to query schema")?;
+                let found: Option<(String, String, Option<String>)> = stmt
+                    .query_row([&name], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                    .optional()
+                    .context("Failed to read schema")?;
+                if let Some((kind, name, sql)) = found {
+                    let columns = table_columns(&conn, &schema, &name);
+                    let name = if schema == "main" { name } else { format!("{}.{}", schema, name) };
+                    return Ok(Some(SchemaObject { kind, name, sql, columns }));
+                }
+            }
+            Ok(None)
+        })
+        .await
+        .context("Failed to execute background task")??;
+
+        let Some(SchemaObject { kind, name, sql, columns }) = definition else {
+            self.status = format!("No table or view named {}", typed);
             return Ok(());
-        }
-
-        let db_path = self.database_path.clone();
-
-        let result =
-            tokio::task::spawn_blocking(move || -> Result<(Vec<String>, Vec<Vec<String>>)> {
-                let conn = Connection::open(&db_path)
-                    .context("Failed to open database in background task")?;
-
-                // Execute all statements except the last one
-                for stmt_sql in &statements[..statements.len() - 1] {
-                    let mut stmt = conn
-                        .prepare(stmt_sql)
-                        .map_err(|e| anyhow::anyhow!(format_sql_error(&e, stmt_sql)))?;
-                    if stmt.column_count() > 0 {
-                        // SELECT-like statement: execute but discard results
-                        let _ = stmt
-                            .query_map([], |_| Ok(()))
-                            .map_err(|e| anyhow::anyhow!(format_sql_error(&e, stmt_sql)))?;
-                    } else {
-                        // Non-SELECT statement: use execute
-                        conn.execute(stmt_sql, [])
-                            .map_err(|e| anyhow::anyhow!(format_sql_error(&e, stmt_sql)))?;
-                    }
-                }
-
-                // Prepare and execute the last statement to get results
-                let last_sql = &statements[statements.len() - 1];
-                let mut stmt = conn
-                    .prepare(last_sql)
-                    .map_err(|e| anyhow::anyhow!(format_sql_error(&e, last_sql)))?;
-                let column_names: Vec<String> =
-                    stmt.column_names().iter().map(|s| s.to_string()).collect();
-
-                let mut results = Vec::new();
-                let rows = stmt.query_map([], |row| {
-                    let mut row_data = Vec::new();
-                    for i in 0..row.as_ref().column_count() {
-                        let value = match row.get_ref(i) {
-                            Ok(rusqlite::types::ValueRef::Null) => String::from("NULL"),
-                            Ok(rusqlite::types::ValueRef::Integer(i)) => i.to_string(),
-                            Ok(rusqlite::types::ValueRef::Real(f)) => f.to_string(),
-                            Ok(rusqlite::types::ValueRef::Text(s)) => {
-                                String::from_utf8_lossy(s).to_string()
-                            },
-                            Ok(rusqlite::types::ValueRef::Blob(_)) => String::from("<BLOB>"),
-                            Err(_) => String::from("<ERROR>"),
-                        };
-                        row_data.push(value);
-                    }
-                    Ok(row_data)
-                });
-
-                match rows {
-                    Ok(mut row_iter) => {
-                        for row in row_iter.by_ref() {
-                            results.push(row.context("Error reading row")?);
-                        }
-                        Ok((column_names, results))
-                    },
-                    Err(e) => Err(anyhow::anyhow!(format_sql_error(&e, last_sql))),
-                }
-            })
-            .await
-            .context("Failed to execute background task")??;
+        };
 
-        self.headers = result.0;
-        self.results = result.1;
-        self.current_row = 0;
-        self.current_col = 0;
-        self.vertical_scroll = 0;
-        self.horizontal_scroll = 0;
-        self.status = format!("{} rows returned", self.results.len());
+        let mut lines: Vec<String> = match sql {
+            Some(sql) => sql.lines().map(ToString::to_string).collect(),
+            None => vec![String::from("<no CREATE statement recorded>")],
+        };
+        lines.push(String::new());
+        lines.push(format!("Columns ({}):", columns.len()));
+        lines.extend(columns.iter().map(|c| format!("  {}", c.name)));
 
+        self.popup = Some(TextPopup::new(format!(" {} {} ", kind, name), lines));
+        self.status = format!("Definition of {} (esc to close)", name);
         Ok(())
     }
-}
 
-fn history_root_dir() -> Result<PathBuf> {
-    if let Ok(dir) = env::var("SQUEAL_CONFIG_DIR") {
-        return Ok(Path::new(&dir).to_path_buf());
-    }
-    if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
-        return Ok(Path::new(&xdg).join("squeal"));
+    /// Shows each column's declared type, NOT NULL flag, default, and primary-key position from
+    /// the loaded schema.
+    fn describe_table(&mut self, table: &str) {
+        let Some(columns) = self.schema.columns_by_table.get(&table.to_lowercase()) else {
+            self.status = format!("No such table: {}", table);
+            return;
+        };
+        let lines = describe_lines(columns);
+        let name = self
+            .schema
+            .tables
+            .iter()
+            .find(|t| t.eq_ignore_ascii_case(table))
+            .cloned()
+            .unwrap_or_else(|| table.to_string());
+        self.popup =
+            Some(TextPopup { wrap: false, ..TextPopup::new(format!(" {} ", name), lines) });
+        self.status = format!("Columns of {} (h/l to scroll, esc to close)", name);
     }
-    let home = env::var("HOME").context("HOME not set")?;
-    Ok(Path::new(&home).join(".config").join("squeal"))
-}
 
-fn resolve_database_path(database: &str) -> Result<PathBuf> {
-    let path = Path::new(database);
-    if path.is_absolute() {
-        return Ok(path.to_path_buf());
+    fn describe_table_under_cursor(&mut self) {
+        match self.identifier_under_cursor() {
+            Some(table) => self.describe_table(&table),
+            None => self.status = String::from("No identifier under cursor"),
+        }
     }
-    Ok(env::current_dir().context("Failed to read current directory")?.join(path))
-}
 
-fn history_file_path_for_database(database_path: &Path) -> Result<PathBuf> {
-    let root = history_root_dir()?;
-    let history_dir = root.join("history-by-db");
-    let candidates = history_file_candidates(&history_dir, database_path);
-    if let Some(existing) = candidates.iter().find(|p| p.exists()) {
-        return Ok(existing.clone());
+    fn handle_popup_key(&mut self, key: crossterm::event::KeyEvent) {
+        let Some(popup) = self.popup.as_mut() else {
+            return;
+        };
+        let max_scroll = popup.lines.len().saturating_sub(1);
+        let max_hscroll = popup.lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.popup = None,
+            KeyCode::Left | KeyCode::Char('h') if !popup.wrap => {
+                popup.hscroll = popup.hscroll.saturating_sub(4)
+            },
+            KeyCode::Right | KeyCode::Char('l') if !popup.wrap => {
+                popup.hscroll = (popup.hscroll + 4).min(max_hscroll.saturating_sub(1))
+            },
+            KeyCode::Up | KeyCode::Char('k') => popup.scroll = popup.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => popup.scroll = (popup.scroll + 1).min(max_scroll),
+            KeyCode::PageUp => popup.scroll = popup.scroll.saturating_sub(10),
+            KeyCode::PageDown => popup.scroll = (popup.scroll + 10).min(max_scroll),
+            _ => {},
+        }
     }
-    Ok(candidates
-        .first()
-        .cloned()
-        .unwrap_or_else(|| history_file_path_with_key(&history_dir, database_path)))
-}
 
-fn history_file_candidates(history_dir: &Path, database_path: &Path) -> Vec<PathBuf> {
-    let mut keys = Vec::<PathBuf>::new();
+    fn open_info(&mut self) -> Result<()> {
+        let conn = lock_connection(&self.conn)?;
+        let pragma = |name: &str| -> Result<String> {
+            let value: rusqlite::types::Value = conn
+                .query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+                .with_context(|| format!("Failed to read PRAGMA {}", name))?;
+            Ok(match value {
+                rusqlite::types::Value::Integer(i) => i.to_string(),
+                rusqlite::types::Value::Text(s) => s,
+                other => format!("{:?}", other),
+            })
+        };
+        let page_size: u64 = pragma("page_size")?.parse().unwrap_or(0);
+        let page_count: u64 = pragma("page_count")?.parse().unwrap_or(0);
+        let database = if self.sandbox_origin.is_some() {
+            format!("{} (sandbox copy)", self.database_path)
+        } else {
+            self.database_path.clone()
+        };
+        let lines = vec![
+            format!("Database      {}", database),
+            format!("SQLite        {}", rusqlite::version()),
+            format!("user_version  {}", read_user_version(&conn)?),
+            format!("Journal mode  {}", pragma("journal_mode")?.to_uppercase()),
+            format!(
+                "Size          {} pages x {} bytes = {} bytes",
+                format_count(page_count as usize),
+                format_count(page_size as usize),
+                format_count((page_count * page_size) as usize)
+            ),
+            format!("Tables        {}", self.schema.tables.len()),
+        ];
+        drop(conn);
+        self.popup = Some(TextPopup::new(" Database info ", lines));
+        Ok(())
+    }
 
-    if let Ok(canonical) = fs::canonicalize(database_path) {
-        keys.push(canonical);
+    fn save_table_style(&mut self, what: &str, enabled: bool) -> Result<()> {
+        self.table_style.write_to(&mut self.config);
+        self.save_config()?;
+        self.status = format!("{} {}", what, if enabled { "on" } else { "off" });
+        Ok(())
     }
-    keys.push(database_path.to_path_buf());
 
-    let mut files = Vec::new();
-    for key in keys {
-        let path = history_file_path_with_key(history_dir, &key);
-        if !files.iter().any(|p: &PathBuf| p == &path) {
-            files.push(path);
+    /// Shows a line diff between two history entries (numbered from 1 = most recent), or by
+    /// default between the previous entry and the editor.
+    fn diff_history(&mut self, entries: Option<(usize, usize)>) {
+        let nth_latest = |n: usize| {
+            n.checked_sub(1)
+                .and_then(|back| self.query_history.len().checked_sub(back + 1))
+                .map(|idx| self.query_history[idx].clone())
+        };
+        let (old, new, title) = match entries {
+            Some((a, b)) => match (nth_latest(a), nth_latest(b)) {
+                (Some(old), Some(new)) => (old, new, format!(" History {} → {} ", a, b)),
+                _ => {
+                    self.status = format!("History has {} entries", self.query_history.len());
+                    return;
+                },
+            },
+            None => {
+                let current = self.current_query();
+                // While browsing, the editor shows an entry; compare with the one before it
+                let previous = match self.history_index {
+                    Some(idx) => idx.checked_sub(1).and_then(|i| self.history_entry(i)),
+                    None => {
+                        self.query_history.iter().rev().find(|q| **q != current).map(|q| q.as_str())
+                    },
+                };
+                let Some(previous) = previous.map(ToString::to_string) else {
+                    self.status = String::from("No earlier query to compare with");
+                    return;
+                };
+                (previous, current, String::from(" Previous query → editor "))
+            },
+        };
+
+        let diff = diff::line_diff(&old, &new);
+        if diff.iter().all(|line| matches!(line, diff::DiffLine::Same(_))) {
+            self.status = String::from("Queries are identical");
+            return;
         }
+        let lines = diff
+            .iter()
+            .map(|line| match line {
+                diff::DiffLine::Same(l) => format!("  {}", l),
+                diff::DiffLine::Removed(l) => format!("- {}", l),
+                diff::DiffLine::Added(l) => format!("+ {}", l),
+            })
+            .collect();
+        self.popup = Some(TextPopup { diff: true, ..TextPopup::new(title, lines) });
     }
-    files
-}
 
-fn history_file_path_with_key(history_dir: &Path, database_path: &Path) -> PathBuf {
-    let db_key = database_path.to_string_lossy();
-    let hash = stable_hash64(db_key.as_bytes());
-    let name = sanitize_history_name(
-        database_path.file_name().and_then(|s| s.to_str()).unwrap_or("database"),
-    );
-    history_dir.join(format!("{}-{:016x}.history", name, hash))
-}
+    /// Shows the statements Enter would send to SQLite, without running them.
+    fn dry_run(&mut self) {
+        let statements = split_statements(&self.current_query());
+        if statements.is_empty() {
+            self.status = String::from("Empty query");
+            return;
+        }
+        // Placeholders show the values being entered, or those of the last run
+        let params = self.bind_prompt.as_ref().map_or(&self.last_params, |p| &p.values);
+        let total = statements.len();
+        let mut lines = Vec::new();
+        let mut substituted = false;
+        let mut unbound = Vec::new();
+        for (i, statement) in statements.into_iter().enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            if total > 1 {
+                lines.push(format!("-- statement {} of {}", i + 1, total));
+            }
+            let statement = if is_unbounded_recursive_cte(&statement) {
+                lines.push(String::from("-- recursive CTE without LIMIT: squeal asks first,"));
+                lines.push(String::from("-- and `y` sends it capped as:"));
+                cap_recursive_cte(statement)
+            } else {
+                statement
+            };
+            let shown = substitute_params(&statement, params);
+            substituted |= shown.substituted;
+            for key in shown.unbound {
+                if !unbound.contains(&key) {
+                    unbound.push(key);
+                }
+            }
+            lines.extend(shown.sql.lines().map(ToString::to_string));
+        }
+        if substituted {
+            lines.push(String::new());
+            lines.push(String::from("-- parameter values shown for display only; `enter` asks"));
+            lines.push(String::from("-- for them and binds them separately"));
+        }
+        if !unbound.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("-- unbound: {} (`enter` asks for values)", unbound.join(", ")));
+        }
+        if let Some(compare) = &self.compare {
+            lines.push(String::new());
+            lines.push(format!("-- also sent to {}", compare.name));
+        }
+        self.popup = Some(TextPopup::new(" Dry run (not executed) ", lines));
+    }
 
-fn sanitize_history_name(name: &str) -> String {
-    let mut out = String::new();
-    for ch in name.chars() {
-        if ch.is_ascii_alphanumeric() || ch == '.' || ch == '-' || ch == '_' {
-            out.push(ch);
-        } else {
-            out.push('_');
+    /// Writes the results as they came from the query (untransposed) to `path`, or to a new
+    /// timestamped file next to the database.
+    fn export_results(&mut self, format: ExportFormat, path: Option<&str>) -> Result<()> {
+        let tab = &self.result_tabs[self.active_tab];
+        let (headers, rows) = match &tab.untransposed {
+            Some((headers, rows)) => (headers, rows),
+            None => (&tab.headers, &tab.results),
+        };
+        if headers.is_empty() {
+            self.status = String::from("No results to export");
+            return Ok(());
         }
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => default_export_path(&self.database_path, format)?,
+        };
+        fs::write(&path, format.render(headers, rows))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        self.status = format!("Exported {} rows to {}", format_count(rows.len()), path.display());
+        Ok(())
     }
-    if out.is_empty() { String::from("database") } else { out }
-}
 
-fn stable_hash64(bytes: &[u8]) -> u64 {
-    let mut hash: u64 = 14695981039346656037;
-    for b in bytes {
-        hash ^= u64::from(*b);
-        hash = hash.wrapping_mul(1099511628211);
+    /// Writes the editor contents to `path` (relative to the working directory), or to the file
+    /// last loaded or saved.
+    fn save_query_file(&mut self, path: Option<&str>) -> Result<()> {
+        let Some(path) = path.map(PathBuf::from).or_else(|| self.query_file.clone()) else {
+            self.status = String::from("Usage: :w <path>");
+            return Ok(());
+        };
+        let text = self.current_query();
+        fs::write(&path, &text).with_context(|| format!("Failed to write {}", path.display()))?;
+        self.status = format!("Wrote {} bytes to {}", format_count(text.len()), path.display());
+        self.query_file = Some(path);
+        self.saved_query = Some(text);
+        Ok(())
     }
-    hash
-}
 
-fn load_query_history(path: &Path) -> Result<Vec<String>> {
-    if !path.exists() {
-        return Ok(Vec::new());
+    /// Replaces the editor contents with the file at `path` (relative to the working directory).
+    /// Like vim, refuses while the editor holds unsaved edits unless `force` (`:e!`).
+    fn load_query_file(&mut self, path: &str, force: bool) -> Result<()> {
+        if !force && self.has_unsaved_query() {
+            self.status = String::from("Unsaved changes (:w to save, :e! to discard)");
+            return Ok(());
+        }
+        let path = PathBuf::from(path);
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        // Editors end files with a newline; don't leave an empty last line in the buffer
+        self.set_query(text.strip_suffix('\n').unwrap_or(&text));
+        self.status = format!("Loaded {}", path.display());
+        self.query_file = Some(path);
+        self.saved_query = Some(self.current_query());
+        Ok(())
     }
-    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
-    Ok(bytes
-        .split(|b| *b == 0)
-        .filter(|chunk| !chunk.is_empty())
-        .map(|chunk| String::from_utf8_lossy(chunk).to_string())
-        .collect())
-}
 
-fn save_query_history(path: &Path, history: &[String]) -> Result<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    /// Whether the editor text would be lost by replacing it: not blank, not what `:e`/`:w` last
+    /// read or wrote, and not already kept in the query history.
+    fn has_unsaved_query(&self) -> bool {
+        let text = self.current_query();
+        !text.trim().is_empty()
+            && self.saved_query.as_deref() != Some(text.as_str())
+            && !self.query_history.contains(&text)
     }
-    let data = history.join("\0");
-    fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))?;
-    Ok(())
-}
 
-fn format_sql_error(err: &rusqlite::Error, sql: &str) -> String {
-    let msg = err.to_string();
-    let sql_excerpt = truncate_right(sql.trim(), 80);
-    let lower = msg.to_lowercase();
+    fn show_paths(&mut self) {
+        let mut lines = vec![format!("Database  {}", self.database_path)];
+        lines.extend(
+            self.attachments.iter().map(|a| format!("Attached  {} as {}", a.path, a.alias)),
+        );
+        lines.extend([
+            match &self.history_path {
+                Some(path) => format!("History   {}", path.display()),
+                None => String::from("History   (not kept for :memory:)"),
+            },
+            format!("Config    {}", self.config_path.display()),
+            String::new(),
+            String::from(":reveal db|history|config opens the containing folder"),
+        ]);
+        self.popup = Some(TextPopup::new(" Paths ", lines));
+    }
 
-    if lower.contains("syntax error") || lower.contains("incomplete input") {
-        return format!("SQL syntax error: {}. Query: {}", msg, sql_excerpt);
+    /// Opens the folder holding the database, history file, or config in the system file
+    /// browser.
+    fn reveal_path(&mut self, which: &str) -> Result<()> {
+        let path = match which {
+            "db" | "database" if is_sqlite_uri(&self.database_path) => {
+                self.status = format!("{} is not a file on disk", self.database_path);
+                return Ok(());
+            },
+            "db" | "database" => PathBuf::from(&self.database_path),
+            "history" => match &self.history_path {
+                Some(path) => path.clone(),
+                None => {
+                    self.status = String::from("No history is kept for :memory:");
+                    return Ok(());
+                },
+            },
+            "config" => self.config_path.clone(),
+            _ => {
+                self.status = format!("Unknown path {} (db, history, or config)", which);
+                return Ok(());
+            },
+        };
+        let dir = path.parent().unwrap_or(&path);
+        let opener = if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(windows) {
+            "explorer"
+        } else {
+            "xdg-open"
+        };
+        std::process::Command::new(opener)
+            .arg(dir)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run {} (path: {})", opener, dir.display()))?;
+        self.status = format!("Opened {}", dir.display());
+        Ok(())
     }
-    if lower.contains("no such table") {
-        return format!("Table not found: {}. Query: {}", msg, sql_excerpt);
+
+    fn open_script_log(&mut self) {
+        if self.script_log.is_empty() {
+            self.status = String::from("No query has run yet");
+            return;
+        }
+        self.popup = Some(TextPopup::new(" Last run ", self.script_log.clone()));
     }
-    if lower.contains("no such column") {
-        return format!("Column not found: {}. Query: {}", msg, sql_excerpt);
+
+    /// Quotes a table name from the schema, keeping an attachment alias as its own identifier.
+    fn quote_table(&self, table: &str) -> String {
+        match table.split_once('.') {
+            Some((alias, name)) if self.attachments.iter().any(|a| a.alias == alias) => {
+                format!("{}.{}", quote_identifier(alias), quote_identifier(name))
+            },
+            _ => quote_identifier(table),
+        }
     }
-    if lower.contains("near \"") {
-        return format!("SQL parse error: {}. Query: {}", msg, sql_excerpt);
+
+    /// Stops a squeal-issued write up front in a read-only session, rather than after a prompt.
+    /// The sandbox copy stays writable.
+    fn refuse_write(&mut self, what: &str) -> bool {
+        let refused = self.read_only && self.sandbox_origin.is_none();
+        if refused {
+            self.status = format!("{} is disabled with --read-only (try :sandbox)", what);
+        }
+        refused
     }
 
-    format!("SQL error: {}. Query: {}", msg, sql_excerpt)
-}
+    fn set_user_version(&mut self, value: &str) -> Result<()> {
+        let Ok(version) = value.parse::<i32>() else {
+            self.status = format!("user_version must be a 32-bit integer, got {}", value);
+            return Ok(());
+        };
+        if self.refuse_write(":user_version <n>") {
+            return Ok(());
+        }
+        let conn = lock_connection(&self.conn)?;
+        let previous = read_user_version(&conn)?;
+        // PRAGMA arguments can't be bound, but `version` is a parsed integer
+        conn.execute_batch(&format!("PRAGMA user_version = {}", version))
+            .context("Failed to set user_version")?;
+        drop(conn);
+        self.status = format!("user_version set to {} (was {})", version, previous);
+        Ok(())
+    }
 
-fn format_user_error(e: &anyhow::Error) -> String {
-    let msg = e.to_string();
-    if msg.starts_with("SQL ")
-        || msg.starts_with("Table not found")
-        || msg.starts_with("Column not found")
-    {
-        msg
-    } else {
-        format!("Error: {}", msg)
+    fn open_settings(&mut self) -> Result<()> {
+        let values = settings::read_all(&*lock_connection(&self.conn)?)?;
+        self.settings = Some(SettingsState { selected: 0, values });
+        self.status =
+            String::from("Settings: enter cycles value, s saves to config, d forgets saved value");
+        Ok(())
     }
-}
 
-fn completion_kind(statement_before: &str) -> CompletionKind {
-    let words = uppercase_words(statement_before);
-    let mut kind = CompletionKind::Keyword;
-    for w in words {
-        match w.as_str() {
-            "SELECT" => kind = CompletionKind::Column,
-            "FROM" | "JOIN" | "INTO" | "UPDATE" => kind = CompletionKind::Table,
-            "ON" => kind = CompletionKind::Column,
-            "WHERE" | "GROUP" | "ORDER" | "HAVING" | "LIMIT" => {
-                kind = CompletionKind::Keyword;
+    fn handle_settings_key(&mut self, key: crossterm::event::KeyEvent) -> Result<()> {
+        let Some(state) = self.settings.as_mut() else {
+            return Ok(());
+        };
+        let selected = state.selected.min(settings::PRAGMA_SETTINGS.len() - 1);
+        let setting = &settings::PRAGMA_SETTINGS[selected];
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.settings = None,
+            KeyCode::Up | KeyCode::Char('k') => state.selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                state.selected = (selected + 1).min(settings::PRAGMA_SETTINGS.len() - 1);
+            },
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let next = settings::next_option(setting, &state.values[selected]);
+                let conn = lock_connection(&self.conn)?;
+                settings::apply_pragma(&conn, setting, next)?;
+                state.values = settings::read_all(&conn)?;
+                self.status = format!("PRAGMA {} = {}", setting.name, state.values[selected]);
+            },
+            KeyCode::Char('s') => {
+                let value = state.values[selected].clone();
+                self.config.set(settings::PRAGMA_SECTION, setting.name, Value::String(value));
+                self.save_config()?;
+                self.status = format!("Saved {} to {}", setting.name, self.config_path.display());
+            },
+            KeyCode::Char('d') => {
+                self.config.remove(settings::PRAGMA_SECTION, setting.name);
+                self.save_config()?;
+                self.status = format!("{} will no longer be set on startup", setting.name);
             },
             _ => {},
         }
+        Ok(())
     }
-    kind
-}
 
-fn uppercase_words(s: &str) -> Vec<String> {
-    let mut out = Vec::new();
-    let mut cur = String::new();
-    for ch in s.chars() {
-        if ch.is_ascii_alphanumeric() || ch == '_' {
-            cur.push(ch.to_ascii_uppercase());
-        } else if !cur.is_empty() {
-            out.push(std::mem::take(&mut cur));
+    /// Writes `config` back to the config file, unless the file failed to load and would be
+    /// replaced by this session's defaults.
+    fn save_config(&self) -> Result<()> {
+        anyhow::ensure!(
+            !self.config_load_failed,
+            "Not saving: {} could not be read at startup (fix it and restart)",
+            self.config_path.display()
+        );
+        self.config.save(&self.config_path)
+    }
+
+    /// Moves focus to a specific pane if `key` is one of the directional focus bindings.
+    fn handle_focus_key(&mut self, key: &crossterm::event::KeyEvent) -> bool {
+        if self.focus_keys.editor.iter().any(|k| k.matches(key)) {
+            self.focus = Pane::Editor;
+            return true;
         }
+        if self.focus_keys.results.iter().any(|k| k.matches(key)) {
+            self.focus = Pane::Results;
+            return true;
+        }
+        false
     }
-    if !cur.is_empty() {
-        out.push(cur);
+
+    fn toggle_split_orientation(&mut self) {
+        self.layout.orientation = match self.layout.orientation {
+            SplitOrientation::Vertical => SplitOrientation::Horizontal,
+            SplitOrientation::Horizontal => SplitOrientation::Vertical,
+        };
+        self.status = match self.layout.orientation {
+            SplitOrientation::Vertical => String::from("Editor above results"),
+            SplitOrientation::Horizontal => String::from("Editor beside results"),
+        };
     }
-    out
-}
 
-fn text_before_cursor(text: &str, line: usize, before_cursor: &str) -> String {
-    let mut out = String::new();
-    for (i, l) in text.lines().enumerate() {
-        if i < line {
-            out.push_str(l);
-            out.push('\n');
-        } else if i == line {
-            out.push_str(before_cursor);
-            break;
-        } else {
-            break;
+    /// Writes the layout to the config file if it changed during this session.
+    fn save_layout_on_exit(&mut self) {
+        if LayoutSettings::from_config(&self.config) == self.layout {
+            return;
         }
+        self.layout.write_to(&mut self.config);
+        // Nowhere left to report a failure (or a refusal) once the terminal is torn down
+        let _ = self.save_config();
     }
-    out
-}
 
-fn qualifier_before_word(before_cursor: &str, word_start: usize) -> Option<String> {
-    if word_start == 0 {
-        return None;
+    fn open_command_line(&mut self) {
+        self.command_line.visible = true;
+        self.command_line.input.clear();
+        self.command_line.prompt = ':';
     }
-    let prefix = &before_cursor[..word_start];
-    let prefix = prefix.strip_suffix('.')?;
-    let q_start =
-        prefix.rfind(|c: char| !c.is_ascii_alphanumeric() && c != '_').map(|i| i + 1).unwrap_or(0);
-    let q = prefix[q_start..].trim();
-    if q.is_empty() { None } else { Some(q.to_string()) }
-}
 
-fn prefix_at_char(s: &str, char_col: usize) -> &str {
-    if char_col == 0 {
-        return "";
+    /// Opens the `/` prompt; the cursor follows the first match as the term is typed.
+    fn open_search(&mut self) {
+        self.open_command_line();
+        self.command_line.prompt = '/';
+        self.tab_mut().search = None;
     }
-    for (count, (idx, _)) in s.char_indices().enumerate() {
-        if count == char_col {
-            return &s[..idx];
-        }
+
+    /// Opens the command line with `input` already typed, e.g. `col ` for column jumps.
+    fn open_command_line_with(&mut self, input: &str) {
+        self.open_command_line();
+        self.command_line.input.push_str(input);
     }
-    s
-}
 
-fn truncate_left(s: &str, max: usize) -> String {
+    fn close_command_line(&mut self) {
+        self.command_line.visible = false;
+        self.command_line.input.clear();
+    }
+
+    /// Returns the entered command once the user presses Enter.
+    fn handle_command_line_key(&mut self, key: crossterm::event::KeyEvent) -> Option<String> {
+        if self.command_line.prompt == '/' {
+            self.handle_search_key(key);
+            return None;
+        }
+        match key.code {
+            KeyCode::Esc => self.close_command_line(),
+            KeyCode::Enter => {
+                let command = self.command_line.input.trim().to_string();
+                self.close_command_line();
+                return Some(command);
+            },
+            KeyCode::Tab => self.complete_command_line(),
+            KeyCode::Backspace if self.command_line.input.is_empty() => self.close_command_line(),
+            KeyCode::Backspace => {
+                self.command_line.input.pop();
+            },
+            KeyCode::Char(ch)
+                if !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && !key.modifiers.contains(KeyModifiers::ALT) =>
+            {
+                self.command_line.input.push(ch);
+            },
+            _ => {},
+        }
+        None
+    }
+
+    fn handle_search_key(&mut self, key: crossterm::event::KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.close_command_line();
+                self.clear_search();
+                return;
+            },
+            KeyCode::Enter => {
+                self.close_command_line();
+                if self.tab().search.is_none() {
+                    self.status.clear();
+                }
+                return;
+            },
+            KeyCode::Backspace if self.command_line.input.is_empty() => {
+                self.close_command_line();
+                return;
+            },
+            KeyCode::Backspace => {
+                self.command_line.input.pop();
+            },
+            KeyCode::Char(ch)
+                if !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && !key.modifiers.contains(KeyModifiers::ALT) =>
+            {
+                self.command_line.input.push(ch);
+            },
+            _ => return,
+        }
+        let term = self.command_line.input.to_lowercase();
+        let tab = self.tab_mut();
+        let origin = tab.search.as_ref().map_or((tab.current_row, tab.current_col), |s| s.origin);
+        if term.is_empty() {
+            tab.search = None;
+            self.status.clear();
+            return;
+        }
+        tab.search = Some(SearchState { term, origin });
+        self.jump_to_match(origin, true, true);
+    }
+
+    fn clear_search(&mut self) {
+        if self.tab_mut().search.take().is_some() {
+            self.status = String::from("Search cleared");
+        }
+    }
+
+    /// Moves to the next (or previous) matching cell after `from` in reading order, wrapping
+    /// around; `inclusive` lets `from` itself match.
+    fn jump_to_match(&mut self, from: (usize, usize), forward: bool, inclusive: bool) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        let Some(term) = tab.search.as_ref().map(|s| s.term.clone()) else {
+            return;
+        };
+        let width = tab.headers.len();
+        let total = tab.results.len() * width;
+        if total == 0 {
+            self.status = format!("No matches for {}", term);
+            return;
+        }
+        let start = from.0 * width + from.1.min(width - 1);
+        let positions: Vec<usize> = (0..total)
+            .filter(|&i| {
+                tab.results[i / width].get(i % width).is_some_and(|c| tab.cell_matches_search(c))
+            })
+            .collect();
+        let next = if forward {
+            positions
+                .iter()
+                .find(|&&i| if inclusive { i >= start } else { i > start })
+                .or(positions.first())
+        } else {
+            positions.iter().rev().find(|&&i| i < start).or(positions.last())
+        };
+        let Some(&next) = next else {
+            self.status = format!("No matches for {}", term);
+            return;
+        };
+        let row = next / width;
+        tab.current_row = row;
+        if row < tab.vertical_scroll {
+            tab.vertical_scroll = row;
+        } else if row >= tab.vertical_scroll + self.visible_rows {
+            tab.vertical_scroll = row + 1 - self.visible_rows.max(1);
+        }
+        self.select_col(next % width);
+        let nth = positions.iter().position(|&i| i == next).unwrap_or(0) + 1;
+        self.status = format!("Match {} of {} for {}", nth, positions.len(), term);
+    }
+
+    /// Completes a `col` argument from the result headers, up to the longest shared prefix.
+    fn complete_command_line(&mut self) {
+        let Some(prefix) = self.command_line.input.strip_prefix("col ") else {
+            return;
+        };
+        let prefix = prefix.trim_start().to_lowercase();
+        let headers = &self.result_tabs[self.active_tab].headers;
+        let matches: Vec<&String> =
+            headers.iter().filter(|h| h.to_lowercase().starts_with(&prefix)).collect();
+        let Some(first) = matches.first() else {
+            self.status = String::from("No matching column");
+            return;
+        };
+        let common = matches.iter().fold(first.as_str(), |common, h| {
+            let len = common
+                .char_indices()
+                .zip(h.chars())
+                .take_while(|((_, a), b)| a.eq_ignore_ascii_case(b))
+                .last()
+                .map_or(0, |((i, a), _)| i + a.len_utf8());
+            &common[..len]
+        });
+        self.command_line.input = format!("col {}", common);
+        if matches.len() > 1 {
+            let names: Vec<&str> = matches.iter().take(8).map(|h| h.as_str()).collect();
+            self.status = format!("Columns: {}", names.join(", "));
+        }
+    }
+
+    /// Focuses the column called `name`: an exact (case-insensitive) match, else the first
+    /// column starting with or containing it.
+    fn jump_to_column(&mut self, name: &str) {
+        let needle = name.to_lowercase();
+        let lower: Vec<String> = self.tab().headers.iter().map(|h| h.to_lowercase()).collect();
+        let found = lower
+            .iter()
+            .position(|h| *h == needle)
+            .or_else(|| lower.iter().position(|h| h.starts_with(&needle)))
+            .or_else(|| lower.iter().position(|h| h.contains(&needle)));
+        match found {
+            Some(idx) => {
+                self.select_col(idx);
+                self.status = format!("Column {}", self.tab().headers[idx]);
+            },
+            None => self.status = format!("No column matching {}", name),
+        }
+    }
+
+    async fn run_command(&mut self, command: &str) -> Result<()> {
+        let mut parts = command.split_whitespace();
+        let name = parts.next().unwrap_or("");
+        let rest: Vec<&str> = parts.collect();
+        match (name, rest.as_slice()) {
+            ("", _) => {},
+            ("col", []) => self.status = String::from("Usage: :col <name>"),
+            // Column names may contain spaces, so take the raw remainder
+            ("col", _) => self.jump_to_column(command.trim_start()[name.len()..].trim()),
+            ("sandbox", []) => self.start_sandbox().await?,
+            ("sandbox", ["discard"]) | ("discard", []) => self.discard_sandbox()?,
+            ("settings", []) => self.open_settings()?,
+            ("info", []) => self.open_info()?,
+            ("log", []) => self.open_script_log(),
+            ("paths", []) => self.show_paths(),
+            ("dry-run", []) => self.dry_run(),
+            ("plan", []) => self.explain_query_plan().await?,
+            ("export", [format, ..]) => match ExportFormat::parse(format) {
+                Some(kind) => {
+                    // Like `col`, the path is the raw remainder so it may contain spaces
+                    let path =
+                        command.trim_start()[name.len()..].trim_start()[format.len()..].trim();
+                    self.export_results(kind, Some(path).filter(|p| !p.is_empty()))?;
+                },
+                None => self.status = format!("Unknown export format {} (csv or json)", format),
+            },
+            ("export", _) => self.status = String::from("Usage: :export csv|json [path]"),
+            ("diff-history", []) => self.diff_history(None),
+            ("diff-history", [a, b]) => match (a.parse(), b.parse()) {
+                (Ok(a), Ok(b)) => self.diff_history(Some((a, b))),
+                _ => self.status = String::from("Usage: :diff-history [<older> <newer>]"),
+            },
+            ("zebra", []) => {
+                self.table_style.zebra = !self.table_style.zebra;
+                self.save_table_style("Zebra striping", self.table_style.zebra)?;
+            },
+            ("separators", []) => {
+                self.table_style.row_separators = !self.table_style.row_separators;
+                self.save_table_style("Row separators", self.table_style.row_separators)?;
+            },
+            ("reveal", [which]) => self.reveal_path(which)?,
+            ("reveal", _) => self.status = String::from("Usage: :reveal db|history|config"),
+            ("user_version", []) => {
+                let version = read_user_version(&*lock_connection(&self.conn)?)?;
+                self.status = format!("user_version = {}", version);
+            },
+            ("user_version", [value]) => self.set_user_version(value)?,
+            ("w" | "write", []) => self.save_query_file(None)?,
+            // Like `col`, the path is the raw remainder so it may contain spaces
+            ("w" | "write", _) => {
+                self.save_query_file(Some(command.trim_start()[name.len()..].trim()))?
+            },
+            ("e" | "edit" | "e!" | "edit!", []) => self.status = String::from("Usage: :e <path>"),
+            ("e" | "edit" | "e!" | "edit!", _) => self
+                .load_query_file(command.trim_start()[name.len()..].trim(), name.ends_with('!'))?,
+            ("describe", []) => self.describe_table_under_cursor(),
+            ("describe", [table]) => self.describe_table(table),
+            ("clear", [table]) => self.confirm_clear_table(table).await?,
+            ("clear", _) => self.status = String::from("Usage: :clear <table>"),
+            _ => self.status = format!("Unknown command: {}", command),
+        }
+        Ok(())
+    }
+
+    async fn start_sandbox(&mut self) -> Result<()> {
+        if self.sandbox_origin.is_some() {
+            self.status = String::from("Sandbox already active (:discard to revert)");
+            return Ok(());
+        }
+        let conn = Arc::clone(&self.conn);
+        let config = self.config.clone();
+        let attachments = self.attachments.clone();
+        let sandbox = tokio::task::spawn_blocking(move || -> Result<Connection> {
+            let conn = lock_connection(&conn)?;
+            let sandbox = clone_into_memory(&conn, &attachments)?;
+            // PRAGMAs are per connection, so the copy needs the saved ones too
+            settings::apply_saved(&sandbox, &config);
+            Ok(sandbox)
+        })
+        .await
+        .context("Failed to execute background task")??;
+
+        let origin = std::mem::replace(&mut self.conn, Arc::new(Mutex::new(sandbox)));
+        self.sandbox_origin = Some(origin);
+        self.status =
+            String::from("Sandbox active: changes go to an in-memory copy (:discard to revert)");
+        Ok(())
+    }
+
+    async fn confirm_clear_table(&mut self, table: &str) -> Result<()> {
+        if self.refuse_write(":clear") {
+            return Ok(());
+        }
+        let Some(table) =
+            self.schema.tables.iter().find(|t| t.eq_ignore_ascii_case(table)).cloned()
+        else {
+            self.status = format!("No such table: {}", table);
+            return Ok(());
+        };
+        let conn = Arc::clone(&self.conn);
+        let sql = format!("SELECT count(*) FROM {}", self.quote_table(&table));
+        let count = tokio::task::spawn_blocking(move || -> Result<i64> {
+            let conn = lock_connection(&conn)?;
+            conn.query_row(&sql, [], |row| row.get(0))
+                .map_err(|e| anyhow::anyhow!(format_sql_error(&e, &sql)))
+        })
+        .await
+        .context("Failed to execute background task")??;
+
+        self.confirm = Some(ConfirmState {
+            message: format!(
+                "Delete all {} rows from {}? This cannot be undone.",
+                format_count(usize::try_from(count).unwrap_or(0)),
+                table
+            ),
+            options: "y delete rows  n / esc cancel",
+            action: ConfirmAction::ClearTable(table),
+        });
+        self.status = String::from("Confirm clearing the table");
+        Ok(())
+    }
+
+    async fn clear_table(&mut self, table: String) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        let sql = format!("DELETE FROM {}", self.quote_table(&table));
+        let (deleted, schema) = tokio::task::spawn_blocking(move || -> Result<(usize, Schema)> {
+            let conn = lock_connection(&conn)?;
+            let deleted =
+                conn.execute(&sql, []).map_err(|e| anyhow::anyhow!(format_sql_error(&e, &sql)))?;
+            Ok((deleted, Self::load_schema(&conn)?))
+        })
+        .await
+        .context("Failed to execute background task")??;
+        self.schema = schema;
+        self.status = format!("Deleted {} rows from {}", format_count(deleted), table);
+        Ok(())
+    }
+
+    fn discard_sandbox(&mut self) -> Result<()> {
+        let Some(origin) = self.sandbox_origin.take() else {
+            self.status = String::from("No sandbox active");
+            return Ok(());
+        };
+        self.conn = origin;
+        let schema = Self::load_schema(&*lock_connection(&self.conn)?)?;
+        self.schema = schema;
+        self.status = String::from("Sandbox discarded, back on the real database");
+        Ok(())
+    }
+
+    fn accept_autocomplete(&mut self) {
+        if !matches!(self.editor_state.mode, EditorMode::Insert) {
+            self.autocomplete.visible = false;
+            return;
+        }
+
+        if !self.autocomplete.visible || self.autocomplete.suggestions.is_empty() {
+            return;
+        }
+
+        let selected = self.autocomplete.selected.min(self.autocomplete.suggestions.len() - 1);
+        let suggestion = &self.autocomplete.suggestions[selected];
+
+        let cursor = &self.editor_state.cursor;
+        let line = cursor.row;
+        let col = cursor.col;
+
+        let text = self.editor_state.lines.to_string();
+        if line >= text.lines().count() {
+            return;
+        }
+
+        let current_line = text.lines().nth(line).unwrap_or("");
+        let before_cursor = prefix_at_char(current_line, col);
+        let word_start = identifier_start(before_cursor);
+        let current_word = &before_cursor[word_start..];
+        let current_word_chars = current_word.chars().count();
+
+        for _ in 0..current_word_chars {
+            use crossterm::event::KeyEvent;
+            self.event_handler
+                .on_key_event(KeyEvent::from(KeyCode::Backspace), &mut self.editor_state);
+        }
+
+        for ch in suggestion.chars() {
+            use crossterm::event::KeyEvent;
+            if ch == ' ' {
+                self.event_handler
+                    .on_key_event(KeyEvent::from(KeyCode::Char(' ')), &mut self.editor_state);
+            } else {
+                self.event_handler
+                    .on_key_event(KeyEvent::from(KeyCode::Char(ch)), &mut self.editor_state);
+            }
+        }
+
+        self.autocomplete.visible = false;
+    }
+
+    async fn execute_query(&mut self) -> Result<()> {
+        let sql = self.editor_state.lines.to_string();
+        if sql.trim().is_empty() {
+            self.status = String::from("Empty query");
+            return Ok(());
+        }
+        self.append_run_query_to_history(&sql);
+
+        let statements = split_statements(&sql);
+        if statements.is_empty() {
+            self.status = String::from("Empty query");
+            return Ok(());
+        }
+
+        let names = parameter_names(&*lock_connection(&self.conn)?, &statements);
+        if !names.is_empty() {
+            self.bind_prompt =
+                Some(BindPrompt { statements, names, values: Vec::new(), input: String::new() });
+            self.status = String::from("Enter bind parameter values (esc to cancel)");
+            return Ok(());
+        }
+        self.run_checked(statements, Vec::new()).await
+    }
+
+    /// Runs `statements`, first asking how to treat a recursive CTE with no LIMIT.
+    async fn run_checked(&mut self, statements: Vec<String>, params: BindParams) -> Result<()> {
+        if statements.iter().any(|s| is_unbounded_recursive_cte(s)) {
+            self.confirm = Some(ConfirmState {
+                message: format!(
+                    "This query has a recursive CTE without a LIMIT and may never finish. Cap its \
+                     output at {} rows?",
+                    format_count(RECURSIVE_CTE_ROW_CAP)
+                ),
+                options: "y cap rows  n run as written  esc cancel",
+                action: ConfirmAction::RecursiveCte(statements, params),
+            });
+            self.status = String::from("Recursive CTE without LIMIT: confirm how to run it");
+            return Ok(());
+        }
+
+        self.run_statements(statements, params, FollowUp::None).await
+    }
+
+    async fn handle_bind_prompt_key(&mut self, key: crossterm::event::KeyEvent) -> Result<()> {
+        let Some(prompt) = self.bind_prompt.as_mut() else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.bind_prompt = None;
+                self.status = String::from("Cancelled: query not run");
+            },
+            KeyCode::Enter => match parse_bind_value(&prompt.input) {
+                Ok(value) => {
+                    let name = prompt.names[prompt.values.len()].clone();
+                    prompt.values.push((name, value));
+                    prompt.input.clear();
+                    if prompt.values.len() == prompt.names.len() {
+                        let BindPrompt { statements, values, .. } =
+                            self.bind_prompt.take().expect("prompt is open");
+                        self.status = String::from("Running query...");
+                        self.run_checked(statements, values).await?;
+                    }
+                },
+                Err(e) => self.status = e,
+            },
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            },
+            KeyCode::Char(ch)
+                if !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && !key.modifiers.contains(KeyModifiers::ALT) =>
+            {
+                prompt.input.push(ch);
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    async fn handle_confirm_key(&mut self, key: crossterm::event::KeyEvent) -> Result<()> {
+        let Some(confirm) = self.confirm.take() else {
+            return Ok(());
+        };
+        match (key.code, confirm.action) {
+            (KeyCode::Esc, _) => self.status = String::from("Cancelled"),
+            (KeyCode::Char('y'), ConfirmAction::RecursiveCte(statements, params)) => {
+                let capped = statements.into_iter().map(cap_recursive_cte).collect();
+                self.status = String::from("Running query...");
+                self.run_statements(capped, params, FollowUp::RecursiveCap).await?;
+            },
+            (KeyCode::Char('n'), ConfirmAction::RecursiveCte(statements, params)) => {
+                self.status = String::from("Running query...");
+                self.run_statements(statements, params, FollowUp::None).await?;
+            },
+            (KeyCode::Char('y'), ConfirmAction::ClearTable(table)) => {
+                self.clear_table(table).await?;
+            },
+            (KeyCode::Char('n'), ConfirmAction::ClearTable(_)) => {
+                self.status = String::from("Cancelled");
+            },
+            (_, action) => self.confirm = Some(ConfirmState { action, ..confirm }),
+        }
+        Ok(())
+    }
+
+    /// Shows how many rows the last statement in the editor returns, leaving the results alone.
+    async fn count_current_query(&mut self) -> Result<()> {
+        let Some(statement) = split_statements(&self.current_query()).pop() else {
+            self.status = String::from("Empty query");
+            return Ok(());
+        };
+        let countable = matches!(
+            sql_tokens(&statement).first(),
+            Some(SqlToken::Word(w)) if ["SELECT", "WITH", "VALUES"]
+                .iter()
+                .any(|keyword| w.eq_ignore_ascii_case(keyword))
+        );
+        if !countable {
+            self.status = String::from("Only SELECT queries can be counted");
+            return Ok(());
+        }
+        let conn = Arc::clone(&self.conn);
+        // On their own lines so a trailing `--` comment can't hide the closing parenthesis
+        let sql = format!("SELECT COUNT(*) FROM (\n{}\n)", statement);
+        let count = tokio::task::spawn_blocking(move || -> Result<i64> {
+            let conn = lock_connection(&conn)?;
+            conn.query_row(&sql, [], |row| row.get(0))
+                .map_err(|e| anyhow::anyhow!(format_sql_error(&e, &sql)))
+        })
+        .await
+        .context("Failed to execute background task")??;
+        self.status = format!("Count: {} rows", format_count(usize::try_from(count).unwrap_or(0)));
+        Ok(())
+    }
+
+    /// Shows `EXPLAIN QUERY PLAN` for the statement under the cursor in a popup, leaving the
+    /// results alone.
+    async fn explain_query_plan(&mut self) -> Result<()> {
+        let Some(statement) = self.statement_under_cursor() else {
+            self.status = String::from("Empty query");
+            return Ok(());
+        };
+        let conn = Arc::clone(&self.conn);
+        let sql = format!("EXPLAIN QUERY PLAN {}", statement);
+        let plan = tokio::task::spawn_blocking(move || -> Result<Vec<(i64, i64, String)>> {
+            let conn = lock_connection(&conn)?;
+            let mut stmt =
+                conn.prepare(&sql).map_err(|e| anyhow::anyhow!(format_sql_error(&e, &sql)))?;
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(3)?)))
+                .and_then(|rows| rows.collect())
+                .map_err(|e| anyhow::anyhow!(format_sql_error(&e, &sql)))
+        })
+        .await
+        .context("Failed to execute background task")??;
+        let mut lines = plan_tree(&plan);
+        if lines.is_empty() {
+            lines.push(String::from("(no plan rows)"));
+        }
+        lines.push(String::new());
+        lines.extend(statement.lines().map(|l| format!("-- {}", l)));
+        self.popup = Some(TextPopup::new(" Query plan ", lines));
+        Ok(())
+    }
+
+    /// Starts or stops re-running the editor's query every `refresh_period`.
+    fn toggle_auto_refresh(&mut self) {
+        if self.tail_interval.take().is_some() {
+            self.refresh_query = None;
+            self.status = String::from("Auto-refresh off");
+            return;
+        }
+        self.tail_interval = Some(self.refresh_period);
+        self.refresh_query = Some(self.current_query());
+        // The first tick then runs what is in the editor, not the query behind the results
+        self.last_statements = None;
+        self.status = format!("Auto-refresh every {}s (R to stop)", self.refresh_period.as_secs());
+    }
+
+    /// Stops an `R` auto-refresh once the query it was started on has been edited; `--tail` keeps
+    /// running whatever the editor holds.
+    fn pause_refresh_if_edited(&mut self) {
+        if self.tail_interval.is_some()
+            && self.refresh_query.as_ref().is_some_and(|query| *query != self.current_query())
+        {
+            self.tail_interval = None;
+            self.refresh_query = None;
+            self.status = String::from("Auto-refresh paused: query edited (R to resume)");
+        }
+    }
+
+    /// Re-runs the statements behind the current results for `--tail`, keeping the cursor and
+    /// marking rows that weren't there before. The first tick runs the query in the editor.
+    async fn refresh_tail(&mut self) -> Result<()> {
+        if self.tail_interval.is_none()
+            || self.confirm.is_some()
+            || self.bind_prompt.is_some()
+            || self.running.is_some()
+            // Older tabs hold earlier queries; only the newest one follows `last_statements`
+            || self.active_tab + 1 < self.result_tabs.len()
+        {
+            return Ok(());
+        }
+        let statements = match &self.last_statements {
+            Some(statements) => statements.clone(),
+            None => split_statements(&self.current_query()),
+        };
+        if statements.is_empty() {
+            return Ok(());
+        }
+        let conn = Arc::clone(&self.conn);
+        let check = statements.clone();
+        let readonly = tokio::task::spawn_blocking(move || -> Result<bool> {
+            Ok(all_read_only(&*lock_connection(&conn)?, &check))
+        })
+        .await
+        .context("Failed to execute background task")??;
+        if !readonly {
+            self.status = String::from("Tail paused: the last query modifies data");
+            return Ok(());
+        }
+
+        let follow_up = FollowUp::Tail { had_results: self.last_statements.is_some() };
+        self.run_statements(statements, self.last_params.clone(), follow_up).await
+    }
+
+    /// Starts `statements` on the blocking pool. With `queries_in_background` this returns
+    /// straight away and `run_app` calls `finish_query` when the task ends; otherwise it waits.
+    async fn run_statements(
+        &mut self,
+        statements: Vec<String>,
+        params: BindParams,
+        follow_up: FollowUp,
+    ) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        let compare_conn = self.compare.as_ref().map(|c| Arc::clone(&c.conn));
+        let mut interrupts = vec![lock_connection(&conn)?.get_interrupt_handle()];
+        if let Some(other) = &compare_conn {
+            interrupts.push(lock_connection(other)?.get_interrupt_handle());
+        }
+        self.last_statements = Some(statements.clone());
+        self.last_params = params.clone();
+        self.script_log.clear();
+        let max_rows = self.max_rows;
+
+        let total = statements.len();
+        let (progress, log) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::task::spawn_blocking(move || -> Result<_> {
+            let result = query_statements(
+                &*lock_connection(&conn)?,
+                &statements,
+                &params,
+                max_rows,
+                Some(&progress),
+            )?;
+            // A failure on the other database is shown in its pane, not as the query error
+            let compared = compare_conn.map(|other| {
+                lock_connection(&other)
+                    .and_then(|other| {
+                        anyhow::ensure!(
+                            all_read_only(&other, &statements),
+                            "Not run: {}",
+                            COMPARE_SKIPPED_WRITE
+                        );
+                        query_statements(&other, &statements, &params, max_rows, None)
+                    })
+                    .map_err(|e| format_user_error(&e))
+            });
+            Ok((result, compared))
+        });
+        self.running = Some(RunningQuery {
+            task,
+            interrupts,
+            cancelled: false,
+            log,
+            started: Instant::now(),
+            total,
+            follow_up,
+        });
+        if self.queries_in_background {
+            self.status = String::from("Running query... (esc to cancel)");
+            return Ok(());
+        }
+        self.wait_for_query().await
+    }
+
+    /// Records a statement of the running script as it finishes, showing it as progress.
+    fn record_progress(&mut self, line: String) {
+        if self.running.as_ref().is_some_and(|running| running.total > 1) {
+            self.status = format!("{} (esc to cancel)", line);
+        }
+        self.script_log.push(line);
+    }
+
+    /// Waits for the running query, if any, and shows its results.
+    async fn wait_for_query(&mut self) -> Result<()> {
+        let Some(running) = self.running.as_mut() else {
+            return Ok(());
+        };
+        let outcome = (&mut running.task).await;
+        let running = self.running.take().expect("query is running");
+        self.finish_query(running, outcome)
+    }
+
+    /// Interrupts the running statements; `finish_query` reports the cancellation.
+    fn cancel_query(&mut self) {
+        let Some(running) = self.running.as_mut() else {
+            return;
+        };
+        running.cancelled = true;
+        for handle in &running.interrupts {
+            handle.interrupt();
+        }
+        self.status = String::from("Cancelling query...");
+    }
+
+    fn finish_query(
+        &mut self,
+        mut running: RunningQuery,
+        outcome: Result<Result<RunOutcome>, tokio::task::JoinError>,
+    ) -> Result<()> {
+        let elapsed = format_elapsed(running.started.elapsed());
+        let total = running.total;
+
+        while let Ok(line) = running.log.try_recv() {
+            self.script_log.push(line);
+        }
+        let (result, compared) = match outcome.context("Failed to execute background task")? {
+            Ok(outcome) => outcome,
+            Err(_) if running.cancelled => {
+                self.status = String::from("Query cancelled");
+                return Ok(());
+            },
+            Err(e) if total > 1 => {
+                let ran = self.script_log.len().saturating_sub(1);
+                anyhow::bail!("{} ({} of {} statements ran, :log for details)", e, ran, total);
+            },
+            Err(e) => return Err(e),
+        };
+
+        let (rows, columns) = (result.rows.len(), result.headers.len());
+        // Runs without columns (writes, DDL) leave the results alone, and a `--tail` refresh
+        // updates its tab in place
+        let mut previous = None;
+        let mut compare_summary = String::new();
+        if !result.headers.is_empty() {
+            let refresh = matches!(running.follow_up, FollowUp::Tail { had_results: true });
+            if !refresh {
+                let sql = self.last_statements.as_ref().and_then(|s| s.last()).cloned();
+                self.open_result_tab(sql.unwrap_or_default());
+            }
+            let row_limit = result.truncated.then_some(self.max_rows);
+            let tab = self.tab_mut();
+            let sql = std::mem::take(&mut tab.sql);
+            let old = std::mem::replace(
+                tab,
+                ResultTab {
+                    sql,
+                    headers: result.headers,
+                    results: result.rows,
+                    boolean_columns: result.boolean_columns,
+                    row_limit,
+                    ..ResultTab::default()
+                },
+            );
+            previous = refresh.then_some(old);
+        }
+        if let (Some(compare), Some(compared)) = (&self.compare, compared) {
+            let tab = &mut self.result_tabs[self.active_tab];
+            let main = if columns > 0 { tab.results.as_slice() } else { &[] };
+            let compared = ComparedResults::new(compared, main);
+            compare_summary = compared.summary(&compare.name);
+            if columns > 0 {
+                tab.compare = Some(compared);
+            }
+        }
+        self.status = match result.changes {
+            Some(changes) => {
+                let last = self.last_statements.as_ref().and_then(|s| s.last());
+                format!(
+                    "{} in {}",
+                    write_summary(last.map_or("", |s| s.as_str()), changes),
+                    elapsed
+                )
+            },
+            None if result.truncated => format!(
+                "Showing first {} of more rows in {} (query was not LIMITed)",
+                format_count(rows),
+                elapsed
+            ),
+            None => format!("{} rows returned in {}", format_count(rows), elapsed),
+        };
+        if transpose_is_useful(columns, rows) {
+            self.status.push_str(" (T to transpose)");
+        }
+        if total > 1 {
+            self.status.push_str(&format!(" ({} statements, :log for details)", total));
+        }
+        self.status.push_str(&compare_summary);
+
+        let tab = &mut self.result_tabs[self.active_tab];
+        match running.follow_up {
+            FollowUp::None => {},
+            FollowUp::RecursiveCap => {
+                if rows >= RECURSIVE_CTE_ROW_CAP {
+                    tab.row_limit = Some(RECURSIVE_CTE_ROW_CAP);
+                    self.status = format!(
+                        "Results limited to {} rows (query had no LIMIT)",
+                        format_count(RECURSIVE_CTE_ROW_CAP)
+                    );
+                }
+            },
+            FollowUp::Tail { .. } => {
+                if let Some(previous) = previous {
+                    tab.changed_rows = unmatched_rows(&tab.results, &previous.results);
+                    tab.search = previous.search;
+                    let last_row = tab.results.len().saturating_sub(1);
+                    let last_col = tab.headers.len().saturating_sub(1);
+                    tab.current_row = previous.current_row.min(last_row);
+                    tab.current_col = previous.current_col.min(last_col);
+                    tab.vertical_scroll = previous.vertical_scroll.min(tab.current_row);
+                    tab.horizontal_scroll = previous.horizontal_scroll.min(tab.current_col);
+                }
+                let changed = tab.changed_rows.iter().filter(|&&c| c).count();
+                self.status = format!(
+                    "{} rows, {} changed (refreshing every {}s)",
+                    format_count(tab.results.len()),
+                    format_count(changed),
+                    self.tail_interval.unwrap_or_default().as_secs()
+                );
+            },
+        }
+        Ok(())
+    }
+
+    /// The result set being shown.
+    fn tab(&self) -> &ResultTab {
+        &self.result_tabs[self.active_tab]
+    }
+
+    fn tab_mut(&mut self) -> &mut ResultTab {
+        &mut self.result_tabs[self.active_tab]
+    }
+
+    /// Makes a new, empty tab for `sql` active, or reuses the first one if nothing ran yet.
+    fn open_result_tab(&mut self, sql: String) {
+        if !self.tab().headers.is_empty() {
+            self.result_tabs.push(ResultTab::default());
+            if self.result_tabs.len() > MAX_RESULT_TABS {
+                self.result_tabs.remove(0);
+            }
+            self.active_tab = self.result_tabs.len() - 1;
+        }
+        self.tab_mut().sql = sql;
+    }
+
+    /// Shows the next (or previous) result tab, wrapping around, where its cursor was left.
+    fn cycle_result_tab(&mut self, forward: bool) {
+        let count = self.result_tabs.len();
+        if count < 2 {
+            self.status = String::from("No other result tabs");
+            return;
+        }
+        self.active_tab = if forward {
+            (self.active_tab + 1) % count
+        } else {
+            (self.active_tab + count - 1) % count
+        };
+        self.status = format!(
+            "Tab {} of {}: {} rows",
+            self.active_tab + 1,
+            count,
+            format_count(self.tab().results.len())
+        );
+    }
+
+    /// Copies the cell under the cursor, or the whole row as tab-separated text.
+    fn copy_selection(&mut self, whole_row: bool) {
+        let tab = &self.result_tabs[self.active_tab];
+        let Some(row) = tab.results.get(tab.current_row) else {
+            self.status = String::from("No results to copy");
+            return;
+        };
+        let (text, what) = if whole_row {
+            let fields: Vec<_> = row.iter().map(CellValue::copy_text).collect();
+            (fields.join("\t"), String::from("row"))
+        } else {
+            let Some(cell) = row.get(tab.current_col) else {
+                self.status = String::from("No results to copy");
+                return;
+            };
+            (cell.copy_text().into_owned(), String::from("cell"))
+        };
+        self.status = match copy_to_clipboard(&mut self.clipboard, text) {
+            Ok(()) => format!("Copied {}", what),
+            Err(e) => format!("Clipboard unavailable: {}", e),
+        };
+    }
+
+    /// Sizes the results viewport for a terminal of `area` without drawing, so navigation is
+    /// right before the first frame (and after a resize).
+    fn fit_viewport(&mut self, area: Rect) {
+        let [_, results, _, _] = main_layout(area, &self.layout, self.editor_collapsed);
+        let (_, results) = split_tab_strip(results, self.result_tabs.len());
+        let (results, _) = split_results_area(results, self.compare.is_some());
+        let tab = &mut self.result_tabs[self.active_tab];
+        self.visible_rows = visible_row_count(results, self.table_style.row_separators);
+        let available_width = (results.width as usize).saturating_sub(2);
+        let widths =
+            column_widths(&tab.headers, &tab.results, tab.boolean_columns(), available_width);
+
+        // Keep the cursor inside the new viewport without leaving blank rows below the data
+        tab.current_row = tab.current_row.min(tab.results.len().saturating_sub(1));
+        tab.vertical_scroll = tab
+            .vertical_scroll
+            .min(tab.results.len().saturating_sub(self.visible_rows))
+            .min(tab.current_row)
+            .max((tab.current_row + 1).saturating_sub(self.visible_rows.max(1)));
+
+        tab.current_col = tab.current_col.min(widths.len().saturating_sub(1));
+        tab.horizontal_scroll = tab.horizontal_scroll.min(tab.current_col);
+        self.visible_cols = fitting_columns(&widths[tab.horizontal_scroll..], available_width);
+        if tab.current_col >= tab.horizontal_scroll + self.visible_cols.max(1) {
+            tab.horizontal_scroll = tab.current_col;
+            self.visible_cols = fitting_columns(&widths[tab.horizontal_scroll..], available_width);
+        }
+    }
+
+    fn reset_results_cursor(&mut self) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        tab.current_row = 0;
+        tab.current_col = 0;
+        tab.vertical_scroll = 0;
+        tab.horizontal_scroll = 0;
+    }
+
+    fn select_prev_row(&mut self) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        if tab.current_row > 0 {
+            tab.current_row -= 1;
+            if tab.current_row < tab.vertical_scroll {
+                tab.vertical_scroll = tab.current_row;
+            }
+        }
+    }
+
+    fn select_next_row(&mut self) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        if tab.current_row + 1 < tab.results.len() {
+            tab.current_row += 1;
+            if tab.current_row >= tab.vertical_scroll + self.visible_rows {
+                tab.vertical_scroll = tab.current_row - self.visible_rows + 1;
+            }
+        }
+    }
+
+    /// Moves the cursor to column `idx`, scrolling it to the left edge if it is out of view.
+    fn select_col(&mut self, idx: usize) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        tab.current_col = idx.min(tab.headers.len().saturating_sub(1));
+        if tab.current_col < tab.horizontal_scroll
+            || tab.current_col >= tab.horizontal_scroll + self.visible_cols
+        {
+            tab.horizontal_scroll = tab.current_col;
+        }
+    }
+
+    fn select_prev_col(&mut self) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        if tab.horizontal_scroll > 0 && tab.current_col == tab.horizontal_scroll {
+            tab.horizontal_scroll -= 1;
+            if tab.current_col > 0 {
+                tab.current_col -= 1;
+            }
+        } else if tab.current_col > tab.horizontal_scroll {
+            tab.current_col -= 1;
+        }
+    }
+
+    fn select_next_col(&mut self) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        if tab.current_col + 1 == tab.horizontal_scroll + self.visible_cols
+            && tab.horizontal_scroll + self.visible_cols < tab.headers.len()
+        {
+            tab.horizontal_scroll += 1;
+        } else if tab.current_col + 1 < tab.headers.len() {
+            tab.current_col += 1;
+        }
+    }
+
+    /// Sorts the results by the focused column, flipping direction on repeated presses. Only the
+    /// fetched rows move; nothing is re-queried.
+    fn sort_by_current_col(&mut self) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        if tab.results.is_empty() || tab.current_col >= tab.headers.len() {
+            return;
+        }
+        let col = tab.current_col;
+        let descending = tab.sort == Some((col, false));
+        let order = sort::sorted_indices(&tab.results, col, descending, self.sort_settings);
+        let mut rows: Vec<Option<Vec<CellValue>>> =
+            std::mem::take(&mut tab.results).into_iter().map(Some).collect();
+        tab.results = order.iter().filter_map(|&i| rows[i].take()).collect();
+        if !tab.changed_rows.is_empty() {
+            tab.changed_rows =
+                order.iter().map(|&i| tab.changed_rows.get(i).copied().unwrap_or(false)).collect();
+        }
+        tab.sort = Some((col, descending));
+        tab.current_row = 0;
+        tab.vertical_scroll = 0;
+        self.status = format!(
+            "Sorted by {} {}",
+            tab.headers[col],
+            if descending { "descending" } else { "ascending" }
+        );
+    }
+
+    /// Opens the whole value of the cell under the cursor, which the table may have cut short.
+    fn open_cell_value(&mut self) {
+        let tab = &self.result_tabs[self.active_tab];
+        let Some(cell) = tab.results.get(tab.current_row).and_then(|r| r.get(tab.current_col))
+        else {
+            self.status = String::from("No cell selected");
+            return;
+        };
+        let header = tab.headers.get(tab.current_col).map_or("", String::as_str);
+        let title = format!(" {} (row {}) ", header, tab.current_row + 1);
+        self.popup = Some(TextPopup::new(title, cell.detail_lines()));
+        self.status = String::from("Cell value (esc to close)");
+    }
+
+    fn toggle_expanded_row(&mut self) {
+        if self.tab().results.is_empty() {
+            return;
+        }
+        self.expanded_row = !self.expanded_row;
+    }
+
+    /// Swaps rows and columns of the displayed results, or restores the original layout.
+    fn toggle_transpose(&mut self) {
+        let tab = &mut self.result_tabs[self.active_tab];
+        if let Some((headers, results)) = tab.untransposed.take() {
+            tab.headers = headers;
+            tab.results = results;
+            tab.sort = None;
+            self.reset_results_cursor();
+            self.status = String::from("Results restored");
+            return;
+        }
+        if tab.headers.is_empty() {
+            return;
+        }
+        if tab.results.len() > TRANSPOSE_MAX_ROWS {
+            self.status = format!(
+                "Too many rows to transpose ({} > {})",
+                format_count(tab.results.len()),
+                TRANSPOSE_MAX_ROWS
+            );
+            return;
+        }
+        let (headers, results) = transpose_results(&tab.headers, &tab.results);
+        let headers = std::mem::replace(&mut tab.headers, headers);
+        let results = std::mem::replace(&mut tab.results, results);
+        tab.untransposed = Some((headers, results));
+        tab.sort = None;
+        self.reset_results_cursor();
+        self.status = String::from("Results transposed (T to restore)");
+    }
+}
+
+/// Aligned `column  type  not null  default  pk` lines for the describe popup.
+fn describe_lines(columns: &[ColumnInfo]) -> Vec<String> {
+    let header = ["column", "type", "not null", "default", "pk"].map(String::from);
+    let rows: Vec<[String; 5]> = columns
+        .iter()
+        .map(|c| {
+            [
+                c.name.clone(),
+                c.decl_type.clone(),
+                if c.not_null { String::from("yes") } else { String::new() },
+                c.default.clone().unwrap_or_default(),
+                if c.pk > 0 { c.pk.to_string() } else { String::new() },
+            ]
+        })
+        .collect();
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            let cells: Vec<String> =
+                row.iter().zip(widths).map(|(cell, w)| format!("{:<w$}", cell)).collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+/// Schema names of the main and attached databases, in `PRAGMA database_list` order (`temp`
+/// left out).
+fn database_names(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA database_list").context("Failed to list databases")?;
+    let names = stmt
+        .query_map([], |row| row.get(1))
+        .context("Failed to list databases")?
+        .filter_map(Result::ok)
+        .filter(|name: &String| name != "temp")
+        .collect();
+    Ok(names)
+}
+
+fn table_columns(conn: &Connection, schema: &str, table: &str) -> Vec<ColumnInfo> {
+    let Ok(mut stmt) = conn.prepare(&format!(
+        "PRAGMA {}.table_info({})",
+        quote_identifier(schema),
+        quote_identifier(table)
+    )) else {
+        return Vec::new();
+    };
+    let rows = stmt.query_map([], |row| {
+        let default = match CellValue::from_sql(row.get_ref(4)?) {
+            CellValue::Null => None,
+            value => Some(value.display().into_owned()),
+        };
+        Ok(ColumnInfo {
+            name: row.get(1)?,
+            decl_type: row.get(2)?,
+            not_null: row.get(3)?,
+            default,
+            pk: row.get(5)?,
+        })
+    });
+    match rows {
+        Ok(rows) => rows.filter_map(Result::ok).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Insert mode unless `--normal` or `[editor] start_in_normal = true` asks for Normal mode.
+fn start_mode(normal_flag: bool, config: &Config) -> EditorMode {
+    let from_config = config.get("editor", "start_in_normal").and_then(Value::as_bool);
+    if normal_flag || from_config == Some(true) { EditorMode::Normal } else { EditorMode::Insert }
+}
+
+fn read_user_version(conn: &Connection) -> Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .context("Failed to read user_version")
+}
+
+/// Opens `path` the way every squeal session does, honouring `--read-only` and
+/// `--busy-timeout`.
+fn open_database(
+    path: &str,
+    busy_timeout_ms: u64,
+    read_only: bool,
+    attachments: &[Attachment],
+) -> Result<Connection> {
+    let conn = if read_only {
+        Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+    } else if is_sqlite_uri(path) {
+        Connection::open_with_flags(path, OpenFlags::default() | OpenFlags::SQLITE_OPEN_URI)
+    } else {
+        Connection::open(path)
+    }
+    .with_context(|| format!("Failed to open database {}", path))?;
+    conn.busy_timeout(Duration::from_millis(busy_timeout_ms))
+        .context("Failed to set busy timeout")?;
+    // Attached files inherit the read-only flag from the main connection
+    for attachment in attachments {
+        conn.execute(
+            &format!("ATTACH DATABASE ?1 AS {}", quote_identifier(&attachment.alias)),
+            [&attachment.path],
+        )
+        .with_context(|| format!("Failed to attach {} as {}", attachment.path, attachment.alias))?;
+    }
+    Ok(conn)
+}
+
+fn lock_connection(conn: &Mutex<Connection>) -> Result<MutexGuard<'_, Connection>> {
+    conn.lock().map_err(|_| anyhow::anyhow!("Database connection is unavailable"))
+}
+
+/// Copies `src` and its attachments into a fresh in-memory database using SQLite's online backup
+/// API, keeping the attachment aliases.
+fn clone_into_memory(src: &Connection, attachments: &[Attachment]) -> Result<Connection> {
+    let mut dst = Connection::open_in_memory().context("Failed to open in-memory database")?;
+    for attachment in attachments {
+        dst.execute(
+            &format!("ATTACH DATABASE ':memory:' AS {}", quote_identifier(&attachment.alias)),
+            [],
+        )
+        .with_context(|| format!("Failed to attach sandbox copy of {}", attachment.alias))?;
+    }
+    let names = std::iter::once("main").chain(attachments.iter().map(|a| a.alias.as_str()));
+    for name in names {
+        let backup = rusqlite::backup::Backup::new_with_names(src, name, &mut dst, name)
+            .context("Failed to start sandbox copy")?;
+        backup
+            .run_to_completion(256, Duration::ZERO, None)
+            .context("Failed to copy database into sandbox")?;
+    }
+    Ok(dst)
+}
+
+/// A database given after the first on the command line.
+#[derive(Clone, Debug, PartialEq)]
+struct Attachment {
+    alias: String,
+    path: String,
+}
+
+/// Names each extra database after its file stem, reduced to a plain identifier and made unique
+/// (`orders.db` and `orders.sqlite` become `orders` and `orders_2`). `main` and `temp` are
+/// reserved by SQLite.
+fn attachments_for(paths: &[String]) -> Vec<Attachment> {
+    let mut taken = vec![String::from("main"), String::from("temp")];
+    paths
+        .iter()
+        .map(|path| {
+            let stem = Path::new(path).file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+            let mut base: String = stem
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+                .collect();
+            if base.is_empty() || base.starts_with(|c: char| c.is_ascii_digit()) {
+                base.insert_str(0, "db");
+            }
+            let mut alias = base.clone();
+            let mut n = 2;
+            while taken.contains(&alias) {
+                alias = format!("{}_{}", base, n);
+                n += 1;
+            }
+            taken.push(alias.clone());
+            Attachment { alias, path: path.clone() }
+        })
+        .collect()
+}
+
+fn history_root_dir() -> Result<PathBuf> {
+    if let Ok(dir) = env::var("SQUEAL_CONFIG_DIR") {
+        return Ok(Path::new(&dir).to_path_buf());
+    }
+    if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
+        return Ok(Path::new(&xdg).join("squeal"));
+    }
+    let home = env::var("HOME").context("HOME not set")?;
+    Ok(Path::new(&home).join(".config").join("squeal"))
+}
+
+/// `<db dir>/<db name>-results-<unix seconds>.<ext>`, so quick exports never overwrite each other.
+/// `:memory:` and `file:` URIs have no directory, so those export to `squeal-results-…` in the
+/// working directory.
+fn default_export_path(database_path: &str, format: ExportFormat) -> Result<PathBuf> {
+    let database_path = if is_sqlite_uri(database_path) {
+        env::current_dir().context("Failed to read current directory")?.join("squeal")
+    } else {
+        PathBuf::from(database_path)
+    };
+    let stem = database_path
+        .file_stem()
+        .map_or_else(|| String::from("squeal"), |s| s.to_string_lossy().into_owned());
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    Ok(database_path.with_file_name(format!("{}-results-{}.{}", stem, secs, format.extension())))
+}
+
+/// `:memory:` and `file:` URIs name databases for SQLite itself, not paths to resolve.
+fn is_sqlite_uri(database: &str) -> bool {
+    database == MEMORY_DATABASE || database.starts_with("file:")
+}
+
+fn resolve_database_path(database: &str) -> Result<PathBuf> {
+    if is_sqlite_uri(database) {
+        return Ok(PathBuf::from(database));
+    }
+    let path = Path::new(database);
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    Ok(env::current_dir().context("Failed to read current directory")?.join(path))
+}
+
+/// The history file for a database, or `None` for `:memory:`. URIs are keyed by their text.
+fn history_file_path_for_database(database_path: &Path) -> Result<Option<PathBuf>> {
+    if database_path == Path::new(MEMORY_DATABASE) {
+        return Ok(None);
+    }
+    let root = history_root_dir()?;
+    let history_dir = root.join("history-by-db");
+    let candidates = history_file_candidates(&history_dir, database_path);
+    if let Some(existing) = candidates.iter().find(|p| p.exists()) {
+        return Ok(Some(existing.clone()));
+    }
+    Ok(Some(
+        candidates
+            .first()
+            .cloned()
+            .unwrap_or_else(|| history_file_path_with_key(&history_dir, database_path)),
+    ))
+}
+
+fn history_file_candidates(history_dir: &Path, database_path: &Path) -> Vec<PathBuf> {
+    let mut keys = Vec::<PathBuf>::new();
+
+    if let Ok(canonical) = fs::canonicalize(database_path) {
+        keys.push(canonical);
+    }
+    keys.push(database_path.to_path_buf());
+
+    let mut files = Vec::new();
+    for key in keys {
+        let path = history_file_path_with_key(history_dir, &key);
+        if !files.iter().any(|p: &PathBuf| p == &path) {
+            files.push(path);
+        }
+    }
+    files
+}
+
+fn history_file_path_with_key(history_dir: &Path, database_path: &Path) -> PathBuf {
+    let db_key = database_path.to_string_lossy();
+    let hash = stable_hash64(db_key.as_bytes());
+    let name = sanitize_history_name(
+        database_path.file_name().and_then(|s| s.to_str()).unwrap_or("database"),
+    );
+    history_dir.join(format!("{}-{:016x}.history", name, hash))
+}
+
+fn sanitize_history_name(name: &str) -> String {
+    let mut out = String::new();
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() || ch == '.' || ch == '-' || ch == '_' {
+            out.push(ch);
+        } else {
+            out.push('_');
+        }
+    }
+    if out.is_empty() { String::from("database") } else { out }
+}
+
+fn stable_hash64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 14695981039346656037;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(1099511628211);
+    }
+    hash
+}
+
+/// First line of length-prefixed history files. Files without it use the legacy NUL-separated
+/// format, which can't represent queries containing NUL bytes.
+const HISTORY_HEADER: &[u8] = b"squeal-history v2\n";
+
+fn load_query_history(path: &Path) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(decode_query_history(&bytes))
+}
+
+fn save_query_history(path: &Path, history: &[String]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, encode_query_history(history))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Each entry is `<byte length>:<query bytes>\n`, so queries round-trip byte for byte.
+fn encode_query_history(history: &[String]) -> Vec<u8> {
+    let mut out = HISTORY_HEADER.to_vec();
+    for query in history {
+        out.extend_from_slice(format!("{}:", query.len()).as_bytes());
+        out.extend_from_slice(query.as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+fn decode_query_history(bytes: &[u8]) -> Vec<String> {
+    let Some(mut rest) = bytes.strip_prefix(HISTORY_HEADER) else {
+        return bytes
+            .split(|b| *b == 0)
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| String::from_utf8_lossy(chunk).to_string())
+            .collect();
+    };
+
+    let mut history = Vec::new();
+    // Stop at the first malformed entry rather than guessing where the next one starts
+    while let Some(colon) = rest.iter().position(|b| *b == b':') {
+        let Some(len) =
+            std::str::from_utf8(&rest[..colon]).ok().and_then(|n| n.parse::<usize>().ok())
+        else {
+            break;
+        };
+        let start = colon + 1;
+        let end = start.saturating_add(len);
+        if rest.get(end) != Some(&b'\n') {
+            break;
+        }
+        history.push(String::from_utf8_lossy(&rest[start..end]).to_string());
+        rest = &rest[end + 1..];
+    }
+    history
+}
+
+/// Prefixes of messages produced by `format_sql_error`; these are shown without an extra "Error:".
+const SQL_ERROR_PREFIXES: &[&str] = &[
+    "SQL ",
+    "Table not found",
+    "Column not found",
+    "Constraint violation",
+    "Database busy",
+    "Database locked",
+    "Database is read-only",
+];
+
+/// Label, extended result code name, and actionable hint for SQLite failures that are opaque
+/// from the message alone.
+fn sqlite_error_hint(err: &rusqlite::Error) -> Option<(&'static str, &'static str, &'static str)> {
+    use rusqlite::ffi;
+
+    let code = err.sqlite_error()?.extended_code;
+    let hint = match code {
+        ffi::SQLITE_CONSTRAINT_FOREIGNKEY => (
+            "Constraint violation",
+            "SQLITE_CONSTRAINT_FOREIGNKEY",
+            "a referenced parent row is missing or a child row still points here; run PRAGMA \
+             foreign_key_list(<table>) to see which constraint applies",
+        ),
+        ffi::SQLITE_CONSTRAINT_UNIQUE => (
+            "Constraint violation",
+            "SQLITE_CONSTRAINT_UNIQUE",
+            "a row with the same value already exists; use INSERT OR REPLACE or an upsert",
+        ),
+        ffi::SQLITE_CONSTRAINT_PRIMARYKEY => (
+            "Constraint violation",
+            "SQLITE_CONSTRAINT_PRIMARYKEY",
+            "a row with the same key already exists; use INSERT OR REPLACE or an upsert",
+        ),
+        ffi::SQLITE_CONSTRAINT_NOTNULL => (
+            "Constraint violation",
+            "SQLITE_CONSTRAINT_NOTNULL",
+            "supply a value for the named column or give it a DEFAULT",
+        ),
+        ffi::SQLITE_CONSTRAINT_CHECK => (
+            "Constraint violation",
+            "SQLITE_CONSTRAINT_CHECK",
+            "the row fails the table's CHECK expression",
+        ),
+        ffi::SQLITE_BUSY | ffi::SQLITE_BUSY_RECOVERY | ffi::SQLITE_BUSY_SNAPSHOT => (
+            "Database busy",
+            "SQLITE_BUSY",
+            "another connection holds a lock; retry, or start squeal with a larger --busy-timeout",
+        ),
+        ffi::SQLITE_LOCKED | ffi::SQLITE_LOCKED_SHAREDCACHE => (
+            "Database locked",
+            "SQLITE_LOCKED",
+            "a conflicting statement on this connection is still active; finish or reset it first",
+        ),
+        ffi::SQLITE_READONLY
+        | ffi::SQLITE_READONLY_RECOVERY
+        | ffi::SQLITE_READONLY_CANTLOCK
+        | ffi::SQLITE_READONLY_ROLLBACK
+        | ffi::SQLITE_READONLY_DBMOVED => (
+            "Database is read-only",
+            "SQLITE_READONLY",
+            "squeal was started with --read-only (try :sandbox), or the database file or its \
+             directory is not writable",
+        ),
+        _ => return None,
+    };
+    Some(hint)
+}
+
+fn format_sql_error(err: &rusqlite::Error, sql: &str) -> String {
+    let msg = err.to_string();
+    let sql_excerpt = truncate_right(sql.trim(), 80);
+    let lower = msg.to_lowercase();
+
+    if let Some((label, code, hint)) = sqlite_error_hint(err) {
+        return format!("{} [{}]: {}. Hint: {}. Query: {}", label, code, msg, hint, sql_excerpt);
+    }
+
+    if lower.contains("syntax error") || lower.contains("incomplete input") {
+        return format!("SQL syntax error: {}. Query: {}", msg, sql_excerpt);
+    }
+    if lower.contains("no such table") {
+        return format!("Table not found: {}. Query: {}", msg, sql_excerpt);
+    }
+    if lower.contains("no such column") {
+        return format!("Column not found: {}. Query: {}", msg, sql_excerpt);
+    }
+    if lower.contains("near \"") {
+        return format!("SQL parse error: {}. Query: {}", msg, sql_excerpt);
+    }
+
+    format!("SQL error: {}. Query: {}", msg, sql_excerpt)
+}
+
+fn format_user_error(e: &anyhow::Error) -> String {
+    let msg = e.to_string();
+    if SQL_ERROR_PREFIXES.iter().any(|prefix| msg.starts_with(prefix)) {
+        msg
+    } else {
+        format!("Error: {}", msg)
+    }
+}
+
+fn completion_kind(statement_before: &str) -> CompletionKind {
+    let words = uppercase_words(statement_before);
+    let mut kind = CompletionKind::Keyword;
+    for w in words {
+        match w.as_str() {
+            "SELECT" => kind = CompletionKind::Column,
+            "FROM" | "JOIN" | "INTO" | "UPDATE" => kind = CompletionKind::Table,
+            "ON" => kind = CompletionKind::Column,
+            "WHERE" | "GROUP" | "ORDER" | "HAVING" | "LIMIT" => {
+                kind = CompletionKind::Keyword;
+            },
+            _ => {},
+        }
+    }
+    kind
+}
+
+/// Words that end a `FROM`/`JOIN` table reference instead of aliasing it.
+const TABLE_REFERENCE_STOP_WORDS: &[&str] = &[
+    "ON",
+    "USING",
+    "WHERE",
+    "JOIN",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "INNER",
+    "OUTER",
+    "CROSS",
+    "NATURAL",
+    "GROUP",
+    "ORDER",
+    "HAVING",
+    "LIMIT",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "WINDOW",
+    "SET",
+    "VALUES",
+    "RETURNING",
+    "INDEXED",
+    "NOT",
+    "SELECT",
+    "FROM",
+];
+
+/// A table named after `FROM` or `JOIN`, with its alias if it has one.
+#[derive(Debug, PartialEq)]
+struct TableReference {
+    /// As written, without quotes; `schema.table` when qualified, e.g. for attached databases.
+    name: String,
+    alias: Option<String>,
+}
+
+/// Tables named in `FROM`/`JOIN` clauses of `statement` (including comma joins), so column
+/// completion can stay within them and resolve `u.` to `users`. Subqueries are skipped.
+fn referenced_tables(statement: &str) -> Vec<TableReference> {
+    let tokens = sql_tokens(statement);
+    let word = |i: usize| match tokens.get(i) {
+        Some(SqlToken::Word(w))
+            if !TABLE_REFERENCE_STOP_WORDS.iter().any(|stop| w.eq_ignore_ascii_case(stop)) =>
+        {
+            Some(w.clone())
+        },
+        _ => None,
+    };
+    let mut tables = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let SqlToken::Word(keyword) = token else {
+            continue;
+        };
+        if !keyword.eq_ignore_ascii_case("FROM") && !keyword.eq_ignore_ascii_case("JOIN") {
+            continue;
+        }
+        let mut next = i + 1;
+        while let Some(name) = word(next) {
+            next += 1;
+            if matches!(&tokens.get(next), Some(SqlToken::Word(w)) if w.eq_ignore_ascii_case("AS"))
+            {
+                next += 1;
+            }
+            let alias = word(next);
+            if alias.is_some() {
+                next += 1;
+            }
+            tables.push(TableReference { name, alias });
+            if tokens.get(next) != Some(&SqlToken::Symbol(',')) {
+                break;
+            }
+            next += 1;
+        }
+    }
+    tables
+}
+
+#[derive(Debug, PartialEq)]
+enum SqlToken {
+    /// An identifier or keyword, unquoted; dotted names (`aux1.orders`) are one word.
+    Word(String),
+    /// Punctuation; a string literal is a single `'`.
+    Symbol(char),
+}
+
+/// Splits SQL into words and symbols, skipping comments.
+fn sql_tokens(sql: &str) -> Vec<SqlToken> {
+    sql_token_spans(sql).into_iter().map(|(_, token)| token).collect()
+}
+
+/// `sql_tokens` with the byte range each token came from. This is the one SQL scanner: statement
+/// splitting, completion context, and table references all read from it. As in SQLite, a doubled
+/// quote inside a string or quoted name is an escaped quote.
+fn sql_token_spans(sql: &str) -> Vec<(std::ops::Range<usize>, SqlToken)> {
+    let is_name_start = |c: char| c.is_alphanumeric() || matches!(c, '_' | '"' | '`' | '[');
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        let token = match ch {
+            c if c.is_whitespace() => continue,
+            '-' if chars.peek().is_some_and(|&(_, c)| c == '-') => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            },
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '*') => {
+                chars.next();
+                let mut prev = ' ';
+                for (_, c) in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                continue;
+            },
+            '\'' => {
+                read_quoted(&mut chars, '\'');
+                SqlToken::Symbol('\'')
+            },
+            c if is_name_start(c) => {
+                let mut name = String::new();
+                let mut part = Some(c);
+                loop {
+                    match part {
+                        Some(quote @ ('"' | '`' | '[')) => {
+                            let close = if quote == '[' { ']' } else { quote };
+                            name.push_str(&read_quoted(&mut chars, close));
+                        },
+                        Some(c) => {
+                            name.push(c);
+                            while let Some((_, c)) =
+                                chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                            {
+                                name.push(c);
+                            }
+                        },
+                        None => {},
+                    }
+                    if chars.next_if(|&(_, c)| c == '.').is_none() {
+                        break;
+                    }
+                    name.push('.');
+                    part = chars.next_if(|&(_, c)| is_name_start(c)).map(|(_, c)| c);
+                }
+                SqlToken::Word(name)
+            },
+            c => SqlToken::Symbol(c),
+        };
+        let end = chars.peek().map_or(sql.len(), |&(i, _)| i);
+        tokens.push((start..end, token));
+    }
+    tokens
+}
+
+/// Reads a string or quoted name whose opening quote was just consumed, through the closing
+/// `close`, and returns its text. A doubled `close` is an escaped one (except for `]`).
+fn read_quoted(chars: &mut std::iter::Peekable<std::str::CharIndices>, close: char) -> String {
+    let mut text = String::new();
+    while let Some((_, c)) = chars.next() {
+        if c == close && (close == ']' || chars.next_if(|&(_, c)| c == close).is_none()) {
+            break;
+        }
+        text.push(c);
+    }
+    text
+}
+
+/// True for a `WITH RECURSIVE` query with no LIMIT anywhere, which SQLite may evaluate forever.
+/// Data-modifying statements are skipped since a trailing LIMIT isn't valid for them.
+fn is_unbounded_recursive_cte(statement: &str) -> bool {
+    let words = uppercase_words(statement);
+    let recursive = words.first().is_some_and(|w| w == "WITH")
+        && words.get(1).is_some_and(|w| w == "RECURSIVE");
+    recursive
+        && !words.iter().any(|w| matches!(w.as_str(), "LIMIT" | "INSERT" | "UPDATE" | "DELETE"))
+}
+
+/// The words and names of `sql` in upper case, leaving out strings and comments.
+fn uppercase_words(sql: &str) -> Vec<String> {
+    sql_tokens(sql)
+        .into_iter()
+        .filter_map(|token| match token {
+            SqlToken::Word(word) => Some(word.to_ascii_uppercase()),
+            SqlToken::Symbol(_) => None,
+        })
+        .collect()
+}
+
+fn text_before_cursor(text: &str, line: usize, before_cursor: &str) -> String {
+    let mut out = String::new();
+    for (i, l) in text.lines().enumerate() {
+        if i < line {
+            out.push_str(l);
+            out.push('\n');
+        } else if i == line {
+            out.push_str(before_cursor);
+            break;
+        } else {
+            break;
+        }
+    }
+    out
+}
+
+/// Byte offset where the identifier (letters, digits, `_`) ending `before_cursor` starts. The
+/// character before it may be multibyte, so this steps over it rather than adding 1.
+fn identifier_start(before_cursor: &str) -> usize {
+    before_cursor
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !c.is_alphanumeric() && c != '_')
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+fn qualifier_before_word(before_cursor: &str, word_start: usize) -> Option<String> {
+    if word_start == 0 {
+        return None;
+    }
+    let prefix = &before_cursor[..word_start];
+    let prefix = prefix.strip_suffix('.')?;
+    let q_start = identifier_start(prefix);
+    let q = prefix[q_start..].trim();
+    if q.is_empty() { None } else { Some(q.to_string()) }
+}
+
+/// Identifier (letters, digits, `_`) touching char column `char_col`, if any.
+fn word_at_char(line: &str, char_col: usize) -> Option<&str> {
+    word_at_char_with(line, char_col, |c| c.is_alphanumeric() || c == '_')
+}
+
+/// `word_at_char` across dots, split into an optional qualifier and the name: the cursor on
+/// either part of `aux1.orders` gives `(Some("aux1"), "orders")`.
+fn qualified_name_at_char(line: &str, char_col: usize) -> Option<(Option<&str>, &str)> {
+    let word = word_at_char_with(line, char_col, |c| c.is_alphanumeric() || c == '_' || c == '.')?
+        .trim_matches('.');
+    match word.split_once('.') {
+        Some((qualifier, name)) if !qualifier.is_empty() && !name.is_empty() => {
+            Some((Some(qualifier), name))
+        },
+        _ if word.is_empty() => None,
+        _ => Some((None, word)),
+    }
+}
+
+fn word_at_char_with(line: &str, char_col: usize, is_word: impl Fn(char) -> bool) -> Option<&str> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut idx = char_col.min(chars.len());
+    if chars.get(idx).is_none_or(|&(_, c)| !is_word(c)) {
+        // Cursor just past the end of a word still counts as on it
+        if idx > 0 && is_word(chars[idx - 1].1) {
+            idx -= 1;
+        } else {
+            return None;
+        }
+    }
+    let mut start = idx;
+    while start > 0 && is_word(chars[start - 1].1) {
+        start -= 1;
+    }
+    let mut end = idx + 1;
+    while end < chars.len() && is_word(chars[end].1) {
+        end += 1;
+    }
+    let byte_start = chars[start].0;
+    let byte_end = chars.get(end).map_or(line.len(), |&(i, _)| i);
+    Some(&line[byte_start..byte_end])
+}
+
+fn prefix_at_char(s: &str, char_col: usize) -> &str {
+    if char_col == 0 {
+        return "";
+    }
+    for (count, (idx, _)) in s.char_indices().enumerate() {
+        if count == char_col {
+            return &s[..idx];
+        }
+    }
+    s
+}
+
+fn truncate_left(s: &str, max: usize) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= max {
+        return s.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    if max == 1 {
+        return "…".to_string();
+    }
+    let start = chars.len().saturating_sub(max - 1);
+    let tail: String = chars[start..].iter().collect();
+    format!("…{}", tail)
+}
+
+fn truncate_right(s: &str, max: usize) -> String {
     let chars: Vec<char> = s.chars().collect();
     if chars.len() <= max {
         return s.to_string();
     }
-    if max == 0 {
-        return String::new();
+    if max == 0 {
+        return String::new();
+    }
+    if max == 1 {
+        return "…".to_string();
+    }
+    let head: String = chars[..max - 1].iter().collect();
+    format!("{}…", head)
+}
+
+/// A one-line strip above the results for the tab titles, once there is more than one tab.
+fn split_tab_strip(area: Rect, tabs: usize) -> (Option<Rect>, Rect) {
+    if tabs < 2 || area.height < 4 {
+        return (None, area);
+    }
+    let [strip, rest] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .areas(area);
+    (Some(strip), rest)
+}
+
+/// Main results area and, with `--compare`, the pane beside it.
+fn split_results_area(area: Rect, compare: bool) -> (Rect, Option<Rect>) {
+    if !compare {
+        return (area, None);
+    }
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    (halves[0], Some(halves[1]))
+}
+
+/// Data rows that fit in the results table, after borders and the header row. Separators take a
+/// line between rows but not after the last one.
+fn visible_row_count(results_area: Rect, row_separators: bool) -> usize {
+    let lines = (results_area.height as usize).saturating_sub(3);
+    if row_separators { lines.div_ceil(2) } else { lines }
+}
+
+/// How many of `widths` fit in `available_width`, counting the one-cell gap between columns.
+fn fitting_columns(widths: &[u16], available_width: usize) -> usize {
+    let mut cumulative = 0;
+    let mut num_visible = 0;
+    for &w in widths {
+        let needed = if num_visible == 0 { w as usize } else { w as usize + 1 };
+        if cumulative + needed <= available_width {
+            cumulative += needed;
+            num_visible += 1;
+        } else {
+            break;
+        }
+    }
+    num_visible
+}
+
+/// Editor, results, key hints, and status areas, in that order.
+fn main_layout(area: Rect, layout: &LayoutSettings, editor_collapsed: bool) -> [Rect; 4] {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(0), Constraint::Length(1), Constraint::Length(1)])
+        .split(area);
+    let panes = if editor_collapsed || layout.orientation == SplitOrientation::Vertical {
+        let editor_height = if editor_collapsed { 3 } else { layout.editor_height };
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(editor_height), Constraint::Min(0)])
+            .split(rows[0])
+    } else {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(layout.editor_width_percent), Constraint::Min(0)])
+            .split(rows[0])
+    };
+    [panes[0], panes[1], rows[1], rows[2]]
+}
+
+/// Pivots a result set so each original column becomes a row labelled by its header.
+fn transpose_results(
+    headers: &[String],
+    results: &[Vec<CellValue>],
+) -> (Vec<String>, Vec<Vec<CellValue>>) {
+    let mut transposed_headers = vec![String::from("column")];
+    transposed_headers.extend((1..=results.len()).map(|i| format!("row {}", i)));
+    let transposed = headers
+        .iter()
+        .enumerate()
+        .map(|(j, header)| {
+            let mut row = vec![CellValue::Text(header.clone())];
+            row.extend(results.iter().map(|r| r.get(j).cloned().unwrap_or(CellValue::Null)));
+            row
+        })
+        .collect();
+    (transposed_headers, transposed)
+}
+
+/// A handful of rows with more columns than rows reads better pivoted, e.g. one summary row.
+fn transpose_is_useful(columns: usize, rows: usize) -> bool {
+    (1..=3).contains(&rows) && columns > rows * 2
+}
+
+/// Footer like `rows 51–100 of 3,420 | cols 1–6 of 14` for half-open visible ranges.
+fn results_position(
+    (start_row, end_row): (usize, usize),
+    total_rows: usize,
+    (start_col, end_col): (usize, usize),
+    total_cols: usize,
+) -> String {
+    if total_cols == 0 {
+        return String::new();
+    }
+    let rows = if total_rows == 0 {
+        String::from("0 rows")
+    } else {
+        format!(
+            "rows {}–{} of {}",
+            format_count(start_row + 1),
+            format_count(end_row.max(start_row + 1)),
+            format_count(total_rows)
+        )
+    };
+    format!(
+        " {} | cols {}–{} of {} ",
+        rows,
+        format_count(start_col + 1),
+        format_count(end_col.max(start_col + 1)),
+        format_count(total_cols)
+    )
+}
+
+fn strip_trailing_semicolons(query: &str) -> &str {
+    query.trim_end_matches(|c: char| c == ';' || c.is_whitespace())
+}
+
+/// Draws `EXPLAIN QUERY PLAN` rows `(id, parent, detail)` as a tree, like the sqlite3 shell.
+fn plan_tree(rows: &[(i64, i64, String)]) -> Vec<String> {
+    let is_last = |idx: usize| rows[idx + 1..].iter().all(|(_, parent, _)| *parent != rows[idx].1);
+    let mut lines = Vec::with_capacity(rows.len());
+    // For each open ancestor, whether it was the last child (so no rail continues below it)
+    let mut stack: Vec<(i64, bool)> = Vec::new();
+    for (idx, (id, parent, detail)) in rows.iter().enumerate() {
+        while stack.last().is_some_and(|(open, _)| open != parent) {
+            stack.pop();
+        }
+        let mut line: String =
+            stack.iter().map(|&(_, last)| if last { "   " } else { "│  " }).collect();
+        let last = is_last(idx);
+        line.push_str(if last { "└─ " } else { "├─ " });
+        line.push_str(detail);
+        lines.push(line);
+        stack.push((*id, last));
+    }
+    lines
+}
+
+fn copy_to_clipboard(clipboard: &mut Option<arboard::Clipboard>, text: String) -> Result<()> {
+    let clipboard = match clipboard {
+        Some(clipboard) => clipboard,
+        None => clipboard.insert(arboard::Clipboard::new()?),
+    };
+    clipboard.set_text(text)?;
+    Ok(())
+}
+
+/// Appends squeal's row cap to a recursive CTE that has no LIMIT of its own. The cap goes on its
+/// own line so a trailing `--` comment can't swallow it.
+fn cap_recursive_cte(statement: String) -> String {
+    if is_unbounded_recursive_cte(&statement) {
+        format!("{}\nLIMIT {}", statement, RECURSIVE_CTE_ROW_CAP)
+    } else {
+        statement
+    }
+}
+
+/// Splits editor text into statements on top-level `;`, ignoring semicolons inside quoted
+/// strings, quoted identifiers, and comments. Fragments holding only comments are dropped.
+fn split_statements(sql: &str) -> Vec<String> {
+    statement_spans(sql).into_iter().map(|span| sql[span].trim().to_string()).collect()
+}
+
+/// The statement containing byte `offset`; past the last `;` it is the last statement.
+fn statement_at(sql: &str, offset: usize) -> Option<String> {
+    let spans = statement_spans(sql);
+    let span = spans.iter().find(|span| offset <= span.end).or(spans.last())?;
+    Some(sql[span.clone()].trim().to_string())
+}
+
+/// Byte offset where the statement `sql` ends in begins: just past its last top-level `;`, so
+/// one inside a string or comment doesn't count.
+fn last_statement_start(sql: &str) -> usize {
+    sql_token_spans(sql)
+        .into_iter()
+        .rev()
+        .find(|(_, token)| *token == SqlToken::Symbol(';'))
+        .map_or(0, |(range, _)| range.end)
+}
+
+/// Index of the statement `statement_at` would pick for `offset`, and how many there are.
+fn statement_index_at(sql: &str, offset: usize) -> Option<(usize, usize)> {
+    let spans = statement_spans(sql);
+    let index = match spans.iter().position(|span| offset <= span.end) {
+        Some(index) => index,
+        None => spans.len().checked_sub(1)?,
+    };
+    Some((index, spans.len()))
+}
+
+/// Byte ranges of the statements in `sql`, excluding their terminating `;`.
+fn statement_spans(sql: &str) -> Vec<std::ops::Range<usize>> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut has_code = false;
+    for (range, token) in sql_token_spans(sql) {
+        if token == SqlToken::Symbol(';') {
+            if has_code {
+                statements.push(start..range.start);
+            }
+            start = range.end;
+            has_code = false;
+        } else {
+            has_code = true;
+        }
+    }
+    if has_code {
+        statements.push(start..sql.len());
+    }
+    statements
+}
+
+/// Marks each row of `rows` that has no counterpart in `other`, pairing duplicates one to one.
+fn unmatched_rows(rows: &[Vec<CellValue>], other: &[Vec<CellValue>]) -> Vec<bool> {
+    let mut available = std::collections::HashMap::<&[CellValue], usize>::new();
+    for row in other {
+        *available.entry(row.as_slice()).or_default() += 1;
+    }
+    rows.iter()
+        .map(|row| match available.get_mut(row.as_slice()) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                false
+            },
+            _ => true,
+        })
+        .collect()
+}
+
+/// True when every statement prepares and leaves the database unchanged.
+fn all_read_only(conn: &Connection, statements: &[String]) -> bool {
+    statements.iter().all(|sql| conn.prepare(sql).is_ok_and(|stmt| stmt.readonly()))
+}
+
+/// Runs `statements` in order, returning the headers and rows of the last one.
+fn query_statements(
+    conn: &Connection,
+    statements: &[String],
+    params: &[(String, rusqlite::types::Value)],
+    max_rows: usize,
+    progress: Option<&tokio::sync::mpsc::UnboundedSender<String>>,
+) -> Result<QueryOutput> {
+    let total = statements.len();
+    let report = |i: usize, sql: &str, outcome: &str| {
+        if let Some(progress) = progress {
+            // Nobody listening just means nobody wants the log
+            let _ = progress.send(format!(
+                "[{}/{}] {} {}",
+                i + 1,
+                total,
+                statement_label(sql),
+                outcome
+            ));
+        }
+    };
+
+    // Execute all statements except the last one
+    for (i, stmt_sql) in statements[..total - 1].iter().enumerate() {
+        match run_script_statement(conn, stmt_sql, params) {
+            Ok(outcome) => report(i, stmt_sql, &outcome),
+            Err(e) => {
+                report(i, stmt_sql, &format!("ERROR: {}", e));
+                return Err(e);
+            },
+        }
+    }
+
+    // Prepare and execute the last statement to get results
+    let last_sql = &statements[total - 1];
+    match read_rows(conn, last_sql, params, max_rows) {
+        Ok(output) => {
+            let count = output.changes.map_or(output.rows.len(), |c| c as usize);
+            report(total - 1, last_sql, &format!("{} rows", format_count(count)));
+            Ok(output)
+        },
+        Err(e) => {
+            report(total - 1, last_sql, &format!("ERROR: {}", e));
+            Err(e)
+        },
+    }
+}
+
+/// Runs a statement whose rows aren't shown, describing what it did.
+fn run_script_statement(
+    conn: &Connection,
+    sql: &str,
+    params: &[(String, rusqlite::types::Value)],
+) -> Result<String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| anyhow::anyhow!(format_sql_error(&e, sql)))?;
+    let values = bound_values(&stmt, params);
+    let sql_error = |e: rusqlite::Error| anyhow::anyhow!(format_sql_error(&e, sql));
+    if stmt.column_count() > 0 {
+        // SELECT-like statement (including `... RETURNING`): step every row so the statement
+        // actually runs and errors raised while stepping surface, but discard the values
+        let mut rows = stmt.query(rusqlite::params_from_iter(values)).map_err(sql_error)?;
+        let mut returned = 0;
+        while rows.next().map_err(sql_error)?.is_some() {
+            returned += 1;
+        }
+        return Ok(if returned > 0 {
+            format!("{} rows", format_count(returned))
+        } else {
+            String::from("ok")
+        });
+    }
+    // Non-SELECT statement: use execute
+    let changed = stmt
+        .execute(rusqlite::params_from_iter(values))
+        .map_err(|e| anyhow::anyhow!(format_sql_error(&e, sql)))?;
+    Ok(if changed > 0 { format!("{} rows", format_count(changed)) } else { String::from("ok") })
+}
+
+/// Runs `sql` and collects its rows, stopping after `max_rows` (0 for no cap).
+fn read_rows(
+    conn: &Connection,
+    sql: &str,
+    params: &[(String, rusqlite::types::Value)],
+    max_rows: usize,
+) -> Result<QueryOutput> {
+    let mut stmt = conn.prepare(sql).map_err(|e| anyhow::anyhow!(format_sql_error(&e, sql)))?;
+    let values = bound_values(&stmt, params);
+    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let boolean_columns: Vec<bool> =
+        stmt.columns().iter().map(|c| c.decl_type().is_some_and(is_boolean_type)).collect();
+    // `changes()` keeps the count of the last DML statement, so only trust it if this one moved
+    // the total (DDL and PRAGMAs don't)
+    let total_before = conn.total_changes();
+
+    let mut results = Vec::new();
+    let rows = stmt.query_map(rusqlite::params_from_iter(values), |row| {
+        let mut row_data = Vec::new();
+        for i in 0..row.as_ref().column_count() {
+            let value = match row.get_ref(i) {
+                Ok(value) => CellValue::from_sql(value),
+                Err(_) => CellValue::Text(String::from("<ERROR>")),
+            };
+            row_data.push(value);
+        }
+        Ok(row_data)
+    });
+
+    match rows {
+        Ok(mut row_iter) => {
+            let mut truncated = false;
+            for row in row_iter.by_ref() {
+                if max_rows > 0 && results.len() == max_rows {
+                    // Dropping the iterator resets the statement, abandoning the rest
+                    truncated = true;
+                    break;
+                }
+                // Writes fail on the first step, so this is where e.g. SQLITE_READONLY shows up
+                results.push(row.map_err(|e| anyhow::anyhow!(format_sql_error(&e, sql)))?);
+            }
+            let changes = column_names
+                .is_empty()
+                .then(|| if conn.total_changes() == total_before { 0 } else { conn.changes() });
+            Ok(QueryOutput {
+                headers: column_names,
+                rows: results,
+                boolean_columns,
+                changes,
+                truncated,
+            })
+        },
+        Err(e) => Err(anyhow::anyhow!(format_sql_error(&e, sql))),
+    }
+}
+
+/// How a statement's `i`th placeholder (from 1) is looked up in `BindParams`.
+fn parameter_key(stmt: &rusqlite::Statement<'_>, i: usize) -> String {
+    stmt.parameter_name(i).map_or_else(|| format!("?{}", i), ToString::to_string)
+}
+
+/// Placeholder keys across `statements` in first-seen order. Statements that don't prepare yet
+/// (e.g. they use a table an earlier statement creates) are skipped and bind NULL when run.
+fn parameter_names(conn: &Connection, statements: &[String]) -> Vec<String> {
+    let mut names = Vec::new();
+    for sql in statements {
+        let Ok(stmt) = conn.prepare(sql) else {
+            continue;
+        };
+        for i in 1..=stmt.parameter_count() {
+            let key = parameter_key(&stmt, i);
+            if !names.contains(&key) {
+                names.push(key);
+            }
+        }
+    }
+    names
+}
+
+/// A statement with its bind placeholders filled in for display, from `substitute_params`.
+struct SubstitutedSql {
+    sql: String,
+    /// Whether any placeholder was replaced by a value.
+    substituted: bool,
+    /// Keys of placeholders `params` has no value for, left as written.
+    unbound: Vec<String>,
+}
+
+/// `sql` with each bind placeholder replaced by its value in `params` as a SQL literal. Keys
+/// follow SQLite's numbering like `parameter_key`: a bare `?` takes the next number after the
+/// largest used so far, counting each new `:name`/`@name`/`$name`.
+fn substitute_params(sql: &str, params: &[(String, rusqlite::types::Value)]) -> SubstitutedSql {
+    let tokens = sql_token_spans(sql);
+    let mut out = SubstitutedSql { sql: String::new(), substituted: false, unbound: Vec::new() };
+    let mut copied = 0;
+    let mut named: Vec<&str> = Vec::new();
+    let mut last_index = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let (range, token) = &tokens[i];
+        let next = tokens.get(i + 1).filter(|(next, _)| next.start == range.end);
+        let (end, key) = match (token, next) {
+            (SqlToken::Symbol('?'), Some((next, SqlToken::Word(n))))
+                if n.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                last_index = last_index.max(n.parse().unwrap_or(0));
+                i += 1;
+                (next.end, format!("?{}", n))
+            },
+            (SqlToken::Symbol('?'), _) => {
+                last_index += 1;
+                (range.end, format!("?{}", last_index))
+            },
+            (SqlToken::Symbol(':' | '@' | '$'), Some((next, SqlToken::Word(_)))) => {
+                let name = &sql[range.start..next.end];
+                if !named.contains(&name) {
+                    named.push(name);
+                    last_index += 1;
+                }
+                i += 1;
+                (next.end, name.to_string())
+            },
+            _ => {
+                i += 1;
+                continue;
+            },
+        };
+        i += 1;
+        out.sql.push_str(&sql[copied..range.start]);
+        match params.iter().find(|(name, _)| *name == key) {
+            Some((_, value)) => {
+                out.sql.push_str(&sql_literal(value));
+                out.substituted = true;
+            },
+            None => {
+                out.sql.push_str(&sql[range.start..end]);
+                if !out.unbound.contains(&key) {
+                    out.unbound.push(key);
+                }
+            },
+        }
+        copied = end;
+    }
+    out.sql.push_str(&sql[copied..]);
+    out
+}
+
+/// `value` written as SQL: text quoted with `'` doubled, blobs as `X'..'`.
+fn sql_literal(value: &rusqlite::types::Value) -> String {
+    use rusqlite::types::Value;
+    match value {
+        Value::Null => String::from("NULL"),
+        Value::Integer(n) => n.to_string(),
+        Value::Real(x) => x.to_string(),
+        Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Blob(bytes) => {
+            format!("X'{}'", bytes.iter().map(|b| format!("{:02X}", b)).collect::<String>())
+        },
     }
-    if max == 1 {
-        return "…".to_string();
+}
+
+/// Values for each of `stmt`'s placeholders in order, NULL where `params` has none.
+fn bound_values(
+    stmt: &rusqlite::Statement<'_>,
+    params: &[(String, rusqlite::types::Value)],
+) -> Vec<rusqlite::types::Value> {
+    (1..=stmt.parameter_count())
+        .map(|i| {
+            let key = parameter_key(stmt, i);
+            params
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map_or(rusqlite::types::Value::Null, |(_, value)| value.clone())
+        })
+        .collect()
+}
+
+/// Text as typed, or a number with an `int:` / `real:` prefix.
+fn parse_bind_value(input: &str) -> Result<rusqlite::types::Value, String> {
+    if let Some(n) = input.strip_prefix("int:") {
+        return n
+            .trim()
+            .parse()
+            .map(rusqlite::types::Value::Integer)
+            .map_err(|_| format!("Not an integer: {}", n.trim()));
     }
-    let start = chars.len().saturating_sub(max - 1);
-    let tail: String = chars[start..].iter().collect();
-    format!("…{}", tail)
+    if let Some(x) = input.strip_prefix("real:") {
+        return x
+            .trim()
+            .parse()
+            .map(rusqlite::types::Value::Real)
+            .map_err(|_| format!("Not a number: {}", x.trim()));
+    }
+    Ok(rusqlite::types::Value::Text(input.to_string()))
 }
 
-fn truncate_right(s: &str, max: usize) -> String {
-    let chars: Vec<char> = s.chars().collect();
-    if chars.len() <= max {
-        return s.to_string();
+/// Status for a statement that returns no rows, e.g. `Updated 42 rows` or `CREATE TABLE done`.
+fn write_summary(sql: &str, changes: u64) -> String {
+    let label = statement_label(sql);
+    let verb = match label.as_str() {
+        "INSERT" | "REPLACE" => "Inserted",
+        "UPDATE" => "Updated",
+        "DELETE" => "Deleted",
+        _ if changes > 0 => "Changed",
+        _ => return format!("{} done", label),
+    };
+    format!("{} {} rows", verb, format_count(changes as usize))
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        format!("{:.1}ms", elapsed.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", elapsed.as_secs_f64())
     }
-    if max == 0 {
-        return String::new();
+}
+
+/// Leading keyword(s) of a statement for the script log, e.g. `CREATE TABLE` or `INSERT`.
+fn statement_label(sql: &str) -> String {
+    let words = uppercase_words(sql);
+    match words.as_slice() {
+        [first, second, ..] if matches!(first.as_str(), "CREATE" | "DROP" | "ALTER") => {
+            format!("{} {}", first, second)
+        },
+        [first, ..] => first.clone(),
+        [] => String::new(),
     }
-    if max == 1 {
-        return "…".to_string();
+}
+
+/// Formats `n` with thousands separators, e.g. `1234567` -> `1,234,567`.
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(ch);
     }
-    let head: String = chars[..max - 1].iter().collect();
-    format!("{}…", head)
+    out
+}
+
+/// Width of each result column: the widest of header and values, capped so one column can't
+/// swallow the whole pane.
+fn column_widths(
+    headers: &[String],
+    results: &[Vec<CellValue>],
+    boolean_columns: &[bool],
+    available_width: usize,
+) -> Vec<u16> {
+    let max_width = MAX_COLUMN_WIDTH.min(available_width).max(1);
+    (0..headers.len())
+        .map(|j| {
+            let mut max_len = headers[j].chars().count();
+            for row in results {
+                if let Some(cell) = row.get(j) {
+                    let boolean = boolean_columns.get(j).copied().unwrap_or(false);
+                    max_len = max_len.max(cell.table_text(boolean).chars().count());
+                }
+            }
+            max_len.clamp(1, max_width) as u16
+        })
+        .collect()
+}
+
+/// Breaks `s` into pieces of at most `width` chars, also splitting at newlines.
+fn wrap_chars(s: &str, width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in s.split('\n') {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            out.push(String::new());
+        }
+        out.extend(chars.chunks(width.max(1)).map(|chunk| chunk.iter().collect::<String>()));
+    }
+    out
+}
+
+/// NULL is drawn dim italic and empty text (`∅`) dim, so neither passes for the text `NULL` or a
+/// blank cell.
+fn placeholder_modifier(cell: &CellValue) -> Modifier {
+    match cell {
+        CellValue::Null => Modifier::DIM | Modifier::ITALIC,
+        CellValue::Text(s) if s.is_empty() => Modifier::DIM,
+        _ => Modifier::empty(),
+    }
+}
+
+/// Single-line rendering of a header or cell, truncated with an ellipsis to `width` chars.
+/// Control characters (newlines, tabs) are flattened so they can't break the row layout.
+fn fit_cell(s: &str, width: usize) -> String {
+    let flat: String = s.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    truncate_right(&flat, width)
 }
 
 fn ui(f: &mut Frame, app: &mut App) {
     let bg = Color::Reset;
-    let text_primary = Color::Rgb(212, 220, 232);
-    let text_muted = Color::Rgb(138, 152, 171);
-    let accent = Color::White;
-    let accent_soft = Color::Rgb(130, 130, 130);
-    let insert_accent = Color::Rgb(152, 195, 121);
-    let warn = Color::Rgb(229, 192, 123);
-    let select_bg = Color::Rgb(56, 63, 79);
-    let panel_bg = Color::Rgb(28, 32, 40);
-
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints([
-            Constraint::Length(10),
-            Constraint::Min(0),
-            Constraint::Length(1),
-            Constraint::Length(1),
-        ])
-        .split(f.area());
-
-    let syntax_highlighter = SyntaxHighlighter::new("charcoal", "sql").ok();
+    let text_primary = app.theme.text;
+    let text_muted = app.theme.muted;
+    let accent = app.theme.accent;
+    let accent_soft = app.theme.border;
+    let insert_accent = app.theme.insert;
+    let warn = app.theme.warn;
+    let highlight_fg = app.theme.highlight_fg;
+    let select_bg = app.theme.selection;
+    let panel_bg = app.theme.panel;
+
+    let chunks = main_layout(f.area(), &app.layout, app.editor_collapsed);
+
+    let syntax_highlighter = SyntaxHighlighter::new(&app.theme.syntax, "sql").ok();
     let mode_str = match app.editor_state.mode {
         EditorMode::Insert => "INSERT",
         EditorMode::Normal => "NORMAL",
@@ -952,7 +4404,7 @@ fn ui(f: &mut Frame, app: &mut App) {
     let focus_border_color = match (app.focus, app.editor_state.mode) {
         (Pane::Editor, EditorMode::Insert) => insert_accent,
         (Pane::Editor, _) => accent,
-        (Pane::Results, EditorMode::Insert) => Color::Rgb(98, 122, 84),
+        (Pane::Results, EditorMode::Insert) => app.theme.insert_dim,
         (Pane::Results, _) => accent_soft,
     };
     let title_color = match app.editor_state.mode {
@@ -961,16 +4413,33 @@ fn ui(f: &mut Frame, app: &mut App) {
         EditorMode::Visual => warn,
         _ => accent,
     };
+    // A sandbox is an in-memory copy, so it is writable even in a read-only session
+    let editor_title = if app.sandbox_origin.is_some() {
+        " Query [sandbox] "
+    } else if app.read_only {
+        " Query [RO] "
+    } else {
+        " Query "
+    };
     let editor_block = Block::default()
         .borders(Borders::ALL)
-        .title(" Query ")
+        .title(editor_title)
         .title(Line::from(format!(" {} ", mode_str.to_lowercase())).alignment(Alignment::Right))
+        .title_bottom(
+            Line::from(Span::styled(
+                format!(" {} ", app.cursor_position_label()),
+                Style::default().fg(text_muted).remove_modifier(Modifier::BOLD),
+            ))
+            .alignment(Alignment::Right),
+        )
         .title_style(Style::default().fg(title_color).add_modifier(Modifier::BOLD))
         .border_style(Style::default().fg(focus_border_color));
     let theme = EditorTheme::default()
         .base(Style::default().bg(bg).fg(text_primary))
         .line_numbers_style(Style::default().fg(text_muted))
         .cursor_style(Style::default().bg(select_bg).fg(text_primary).add_modifier(Modifier::BOLD))
+        // Matches the VISUAL title colour and stays readable over syntax highlighting
+        .selection_style(Style::default().bg(warn).fg(highlight_fg))
         .hide_status_line()
         .block(editor_block);
     EditorView::new(&mut app.editor_state)
@@ -978,81 +4447,250 @@ fn ui(f: &mut Frame, app: &mut App) {
         .theme(theme)
         .render(chunks[0], f.buffer_mut());
 
-    app.visible_rows = (chunks[1].height as usize).saturating_sub(3);
-
-    let title = if app.headers.is_empty() { " Results (No data) " } else { " Results " };
-
-    let header_style = Style::default().fg(accent).add_modifier(Modifier::BOLD);
-
-    // Calculate column widths: max of header and data lengths, minimum 30
-    let mut widths = vec![];
-    for j in 0..app.headers.len() {
-        let mut max_len = app.headers[j].len();
-        for row in &app.results {
-            if j < row.len() {
-                max_len = max_len.max(row[j].len());
-            }
-        }
-        widths.push(max_len as u16);
+    let (tab_strip, results_pane) = split_tab_strip(chunks[1], app.result_tabs.len());
+    if let Some(strip) = tab_strip {
+        let titles = app.result_tabs.iter().enumerate().map(|(i, tab)| {
+            let sql = tab.sql.split_whitespace().collect::<Vec<_>>().join(" ");
+            format!("{} {}", i + 1, fit_cell(&sql, 24))
+        });
+        f.render_widget(
+            Tabs::new(titles)
+                .select(app.active_tab)
+                .style(Style::default().fg(text_muted))
+                .highlight_style(Style::default().fg(accent).add_modifier(Modifier::BOLD)),
+            strip,
+        );
+    }
+    let (results_area, compare_area) = split_results_area(results_pane, app.compare.is_some());
+    app.visible_rows = visible_row_count(results_area, app.table_style.row_separators);
+    let tab = &app.result_tabs[app.active_tab];
+
+    let mut title = String::from(" Results ");
+    if tab.headers.is_empty() {
+        title.push_str("(No data) ");
+    } else if tab.untransposed.is_some() {
+        title.push_str("(transposed) ");
+    }
+    if let Some(limit) = tab.row_limit {
+        title.push_str(&format!("[limited to {}] ", format_count(limit)));
     }
 
-    let start_row = app.vertical_scroll;
-    let end_row = (start_row + app.visible_rows).min(app.results.len());
-    let start_col = app.horizontal_scroll;
+    let header_style = Style::default().fg(app.theme.header).add_modifier(Modifier::BOLD);
 
-    // Determine how many columns fit in the available width
-    let available_width = chunks[1].width as usize;
-    let mut cumulative = 0;
-    let mut num_visible = 0;
-    for &w in &widths[start_col..] {
-        if cumulative + w as usize <= available_width {
-            cumulative += w as usize;
-            num_visible += 1;
-        } else {
-            break;
-        }
-    }
+    // Inner width excludes the block borders
+    let available_width = (results_area.width as usize).saturating_sub(2);
+    let widths = column_widths(&tab.headers, &tab.results, tab.boolean_columns(), available_width);
+
+    let start_row = tab.vertical_scroll;
+    let end_row = (start_row + app.visible_rows).min(tab.results.len());
+    let start_col = tab.horizontal_scroll.min(widths.len());
+    let num_visible = fitting_columns(&widths[start_col..], available_width);
     app.visible_cols = num_visible;
-    let end_col = (start_col + num_visible).min(app.headers.len());
+    let end_col = (start_col + num_visible).min(tab.headers.len());
 
-    let headers_slice = &app.headers[start_col..end_col];
+    let headers_slice = &tab.headers[start_col..end_col];
     let widths_slice = &widths[start_col..end_col];
     let constraints: Vec<Constraint> =
         widths_slice.iter().map(|&w| Constraint::Length(w)).collect();
 
     let table = Table::new(
-        app.results[start_row..end_row].iter().enumerate().map(|(i, row)| {
+        tab.results[start_row..end_row].iter().enumerate().map(|(i, row)| {
             let global_i = i + start_row;
             let row_end = start_col + headers_slice.len().min(row.len().saturating_sub(start_col));
-            let row_slice: &[String] =
+            let row_slice: &[CellValue] =
                 if start_col < row.len() { &row[start_col..end_col.min(row_end)] } else { &[] };
             Row::new(row_slice.iter().enumerate().map(|(j, cell)| {
                 let local_j = j + start_col;
-                let base_style = if global_i.is_multiple_of(2) {
-                    Style::default().fg(text_primary)
+                let base_style = if tab.changed_rows.get(global_i).copied().unwrap_or(false) {
+                    Style::default().fg(warn)
+                } else if !app.table_style.zebra || global_i.is_multiple_of(2) {
+                    Style::default().fg(app.theme.row)
                 } else {
-                    Style::default().fg(text_muted)
+                    Style::default().fg(app.theme.row_alt)
                 };
-                let mut cell = Cell::from(cell.as_str()).style(base_style);
-                if global_i == app.current_row && local_j == app.current_col {
-                    cell = cell.style(Style::default().fg(text_primary).bg(select_bg));
-                }
-                cell
+                let width = widths_slice.get(j).copied().unwrap_or_default() as usize;
+                let style = if global_i == tab.current_row && local_j == tab.current_col {
+                    Style::default().fg(text_primary).bg(select_bg)
+                } else if tab.cell_matches_search(cell) {
+                    Style::default().fg(highlight_fg).bg(warn)
+                } else {
+                    base_style
+                };
+                let boolean = tab.boolean_columns().get(local_j).copied().unwrap_or(false);
+                Cell::from(fit_cell(&cell.table_text(boolean), width))
+                    .style(style.add_modifier(placeholder_modifier(cell)))
             }))
+            .bottom_margin(u16::from(app.table_style.row_separators))
         }),
         constraints,
     )
-    .header(Row::new(headers_slice.iter().map(|h| Cell::from(h.as_str()))).style(header_style))
-    .block(Block::default().borders(Borders::ALL).title(title).border_style(
-        Style::default().fg(match app.focus {
-            Pane::Results => accent,
-            Pane::Editor => accent_soft,
-        }),
-    ));
+    .header(
+        Row::new(headers_slice.iter().zip(widths_slice).enumerate().map(|(j, (h, &w))| {
+            match tab.sort {
+                // Truncate the name rather than the arrow
+                Some((col, descending)) if col == start_col + j => Cell::from(format!(
+                    "{} {}",
+                    fit_cell(h, (w as usize).saturating_sub(2)),
+                    if descending { "▼" } else { "▲" }
+                )),
+                _ => Cell::from(fit_cell(h, w as usize)),
+            }
+        }))
+        .style(header_style),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_bottom(
+                Line::from(results_position(
+                    (start_row, end_row),
+                    tab.results.len(),
+                    (start_col, end_col),
+                    tab.headers.len(),
+                ))
+                .alignment(Alignment::Right)
+                .style(Style::default().fg(text_muted)),
+            )
+            .border_style(Style::default().fg(match app.focus {
+                Pane::Results => accent,
+                Pane::Editor => accent_soft,
+            })),
+    );
+
+    if app.expanded_row
+        && let Some(row) = tab.results.get(tab.current_row)
+    {
+        let inner_width = (results_area.width as usize).saturating_sub(2);
+        let inner_height = (results_area.height as usize).saturating_sub(2);
+        let label_width =
+            tab.headers.iter().map(|h| h.chars().count()).max().unwrap_or(0).min(inner_width / 3);
+        let value_width = inner_width.saturating_sub(label_width + 2).max(1);
+        let mut lines = Vec::new();
+        let mut selected_line = 0;
+        for (j, (header, cell)) in tab.headers.iter().zip(row).enumerate() {
+            let selected = j == tab.current_col;
+            if selected {
+                selected_line = lines.len();
+            }
+            let value_style = match (selected, cell) {
+                (true, _) => Style::default().fg(text_primary).bg(select_bg),
+                (false, cell) if tab.cell_matches_search(cell) => {
+                    Style::default().fg(highlight_fg).bg(warn)
+                },
+                (false, CellValue::Null) => Style::default().fg(text_muted),
+                (false, _) => Style::default().fg(text_primary),
+            }
+            .add_modifier(placeholder_modifier(cell));
+            let boolean = tab.boolean_columns().get(j).copied().unwrap_or(false);
+            for (k, chunk) in
+                wrap_chars(&cell.table_text(boolean), value_width).into_iter().enumerate()
+            {
+                let label = if k == 0 { fit_cell(header, label_width) } else { String::new() };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{:<width$}  ", label, width = label_width), header_style),
+                    Span::styled(chunk, value_style),
+                ]));
+            }
+        }
+        // Keep the selected field on screen, scrolling as little as possible
+        let scroll = selected_line.saturating_sub(inner_height.saturating_sub(1));
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(
+                " Row {} of {} (x for table) ",
+                format_count(tab.current_row + 1),
+                format_count(tab.results.len())
+            ))
+            .border_style(Style::default().fg(match app.focus {
+                Pane::Results => accent,
+                Pane::Editor => accent_soft,
+            }));
+        f.render_widget(
+            Paragraph::new(lines)
+                .scroll((u16::try_from(scroll).unwrap_or(u16::MAX), 0))
+                .block(block),
+            results_area,
+        );
+    } else {
+        f.render_widget(table, results_area);
+    }
+    if app.table_style.row_separators && !app.expanded_row {
+        // Rows are laid out with a one-line bottom margin; draw into the gaps
+        let inner = results_area.inner(ratatui::layout::Margin::new(1, 1));
+        let shown = end_row - start_row;
+        for i in 1..shown {
+            let y = inner.y + 1 + (i as u16) * 2 - 1;
+            if y >= inner.bottom() {
+                break;
+            }
+            for x in inner.left()..inner.right() {
+                f.buffer_mut()[(x, y)].set_symbol("─").set_style(Style::default().fg(select_bg));
+            }
+        }
+    }
 
-    f.render_widget(table, chunks[1]);
+    if let (Some(pane), Some(area)) = (&app.compare, compare_area) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} ", pane.name))
+            .border_style(Style::default().fg(accent_soft));
+        if let Some(compare) = &tab.compare
+            && let Some(error) = &compare.error
+        {
+            f.render_widget(
+                Paragraph::new(error.as_str())
+                    .style(Style::default().fg(warn))
+                    .wrap(Wrap { trim: true })
+                    .block(block),
+                area,
+            );
+        } else if let Some(compare) = &tab.compare {
+            // Scrolls in step with the main results so rows line up
+            let available_width = (area.width as usize).saturating_sub(2);
+            let widths = column_widths(
+                &compare.headers,
+                &compare.results,
+                &compare.boolean_columns,
+                available_width,
+            );
+            let start_col = tab.horizontal_scroll.min(widths.len());
+            let start_row = tab.vertical_scroll.min(compare.results.len());
+            let end_row = (start_row + app.visible_rows).min(compare.results.len());
+            let constraints: Vec<Constraint> =
+                widths[start_col..].iter().map(|&w| Constraint::Length(w)).collect();
+            let rows = compare.results[start_row..end_row].iter().enumerate().map(|(i, row)| {
+                let style = if compare.unmatched.get(start_row + i).copied().unwrap_or(false) {
+                    Style::default().fg(warn)
+                } else {
+                    Style::default().fg(text_muted)
+                };
+                let cells = row.iter().enumerate().skip(start_col).zip(&widths[start_col..]);
+                Row::new(cells.map(|((j, cell), &w)| {
+                    let boolean = compare.boolean_columns.get(j).copied().unwrap_or(false);
+                    Cell::from(fit_cell(&cell.table_text(boolean), w as usize))
+                        .style(Style::default().add_modifier(placeholder_modifier(cell)))
+                }))
+                .style(style)
+            });
+            let table = Table::new(rows, constraints)
+                .header(
+                    Row::new(
+                        compare.headers[start_col..]
+                            .iter()
+                            .zip(&widths[start_col..])
+                            .map(|(h, &w)| Cell::from(fit_cell(h, w as usize))),
+                    )
+                    .style(header_style),
+                )
+                .block(block);
+            f.render_widget(table, area);
+        } else {
+            f.render_widget(block, area);
+        }
+    }
 
-    let key_style = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+    let key_style = Style::default().fg(app.theme.key).add_modifier(Modifier::BOLD);
     let hint_style = Style::default().fg(text_muted);
     let hints_spans: Vec<Span> = match app.editor_state.mode {
         EditorMode::Insert => vec![
@@ -1079,7 +4717,11 @@ fn ui(f: &mut Frame, app: &mut App) {
             Span::styled("n", key_style),
             Span::styled(" new query  ", hint_style),
             Span::styled("t", key_style),
-            Span::styled(" tables", hint_style),
+            Span::styled(" tables  ", hint_style),
+            Span::styled("z", key_style),
+            Span::styled(" zoom results  ", hint_style),
+            Span::styled(":", key_style),
+            Span::styled(" command", hint_style),
         ],
     };
     let hints_line = Paragraph::new(Line::from(hints_spans))
@@ -1088,14 +4730,27 @@ fn ui(f: &mut Frame, app: &mut App) {
     f.render_widget(hints_line, chunks[2]);
 
     let width = chunks[3].width as usize;
-    let right_full = app.database_path.clone();
+    let mut right_full = if app.read_only && app.sandbox_origin.is_none() {
+        format!("[RO] {}", app.database_path)
+    } else {
+        app.database_path.clone()
+    };
+    if let Some(interval) = app.tail_interval {
+        right_full.insert_str(0, &format!("⟳ {}s  ", interval.as_secs()));
+    }
     let right = truncate_left(&right_full, width);
-    let status_text = if width <= right.len() {
+    let status_left = if app.command_line.visible {
+        format!("{}{}", app.command_line.prompt, app.command_line.input)
+    } else {
+        app.status.clone()
+    };
+    let right_width = right.chars().count();
+    let status_text = if width <= right_width {
         right
     } else {
-        let left_max = width.saturating_sub(right.len() + 1);
-        let left = truncate_right(&app.status, left_max);
-        let spaces = width.saturating_sub(left.len() + right.len());
+        let left_max = width.saturating_sub(right_width + 1);
+        let left = truncate_right(&status_left, left_max);
+        let spaces = width.saturating_sub(left.chars().count() + right_width);
         format!("{}{}{}", left, " ".repeat(spaces), right)
     };
     let status = Paragraph::new(status_text)
@@ -1112,9 +4767,8 @@ fn ui(f: &mut Frame, app: &mut App) {
         let cursor_row = cursor.row as u16;
         let cursor_col = cursor.col as u16;
 
-        let desired_width =
-            app.autocomplete.suggestions.iter().map(|s| s.len()).max().unwrap_or(20).max(20) as u16;
-        let desired_height = app.autocomplete.suggestions.len().min(8) as u16;
+        let (desired_width, desired_height) =
+            app.autocomplete_limits.popup_size(&app.autocomplete.suggestions);
         let editor = chunks[0];
         let editor_right = editor.x.saturating_add(editor.width);
         let editor_bottom = editor.y.saturating_add(editor.height);
@@ -1147,22 +4801,18 @@ fn ui(f: &mut Frame, app: &mut App) {
                 .collect();
 
             let list = List::new(items).highlight_style(Style::default().bg(select_bg));
+            // Scrolls the list so the selection stays inside a capped popup
+            let mut list_state =
+                ListState::default().with_selected(Some(app.autocomplete.selected));
 
             f.render_widget(Clear, popup_area);
-            f.render_widget(list, popup_area);
+            f.render_stateful_widget(list, popup_area, &mut list_state);
         }
     }
 
     if matches!(app.editor_state.mode, EditorMode::Normal) && app.table_picker.visible {
         let tables = app.filtered_tables();
-        let area = f.area();
-        let width: u16 = 56;
-        let height: u16 = 16;
-        let popup_width = width.min(area.width.saturating_sub(2));
-        let popup_height = height.min(area.height.saturating_sub(2));
-        let popup_x = area.x + area.width.saturating_sub(popup_width) / 2;
-        let popup_y = area.y + area.height.saturating_sub(popup_height) / 2;
-        let popup = Rect::new(popup_x, popup_y, popup_width, popup_height);
+        let popup = centered_rect(f.area(), 56, 16);
 
         if popup.width >= 3 && popup.height >= 3 {
             f.render_widget(Clear, popup);
@@ -1206,6 +4856,189 @@ fn ui(f: &mut Frame, app: &mut App) {
             f.render_widget(List::new(items), sections[1]);
         }
     }
+
+    if matches!(app.editor_state.mode, EditorMode::Normal) && app.history_picker.visible {
+        let entries = app.filtered_history();
+        let popup = centered_rect(f.area(), 80, 20);
+
+        if popup.width >= 3 && popup.height >= 3 {
+            f.render_widget(Clear, popup);
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title(" History ")
+                .border_style(Style::default().fg(accent));
+            let inner = block.inner(popup);
+            f.render_widget(block, popup);
+            let sections = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(1)])
+                .split(inner);
+
+            let filter = Paragraph::new(format!("Filter: {}", app.history_picker.filter))
+                .style(Style::default().fg(warn));
+            f.render_widget(filter, sections[0]);
+
+            let width = sections[1].width as usize;
+            let items: Vec<ListItem> = if entries.is_empty() {
+                vec![ListItem::new("<no matches>").style(Style::default().fg(text_muted))]
+            } else {
+                entries
+                    .iter()
+                    .map(|&i| {
+                        let one_line =
+                            app.query_history[i].split_whitespace().collect::<Vec<_>>().join(" ");
+                        ListItem::new(truncate_right(&one_line, width))
+                            .style(Style::default().fg(text_primary))
+                    })
+                    .collect()
+            };
+            let list =
+                List::new(items).highlight_style(Style::default().bg(select_bg).fg(text_primary));
+            let mut list_state =
+                ListState::default().with_selected(Some(app.history_picker.selected));
+            f.render_stateful_widget(list, sections[1], &mut list_state);
+        }
+    }
+
+    if let Some(state) = &app.settings {
+        let height = settings::PRAGMA_SETTINGS.len() as u16 + 4;
+        let popup = centered_rect(f.area(), 56, height);
+        if popup.width >= 3 && popup.height >= 3 {
+            f.render_widget(Clear, popup);
+            let mut lines: Vec<Line> = settings::PRAGMA_SETTINGS
+                .iter()
+                .zip(&state.values)
+                .enumerate()
+                .map(|(i, (setting, value))| {
+                    let saved = app.config.get(settings::PRAGMA_SECTION, setting.name).is_some();
+                    let text = format!(
+                        " {:<14} {:<12} {}",
+                        setting.name,
+                        value,
+                        if saved { "saved" } else { "" }
+                    );
+                    let style = if i == state.selected {
+                        Style::default().bg(select_bg).fg(text_primary)
+                    } else {
+                        Style::default().fg(text_primary)
+                    };
+                    Line::styled(text, style)
+                })
+                .collect();
+            lines.push(Line::default());
+            lines.push(Line::styled(
+                " enter cycle  s save  d forget  esc close",
+                Style::default().fg(text_muted),
+            ));
+            let paragraph = Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Settings ")
+                    .border_style(Style::default().fg(accent)),
+            );
+            f.render_widget(paragraph, popup);
+        }
+    }
+
+    if let Some(confirm) = &app.confirm {
+        let popup = centered_rect(f.area(), 60, 7);
+        if popup.width >= 3 && popup.height >= 3 {
+            f.render_widget(Clear, popup);
+            let lines = vec![
+                Line::styled(confirm.message.as_str(), Style::default().fg(text_primary)),
+                Line::default(),
+                Line::styled(confirm.options, Style::default().fg(text_muted)),
+            ];
+            let paragraph = Paragraph::new(lines)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(" Confirm ")
+                        .border_style(Style::default().fg(warn)),
+                )
+                .wrap(Wrap { trim: true });
+            f.render_widget(paragraph, popup);
+        }
+    }
+
+    if let Some(prompt) = &app.bind_prompt {
+        let popup = centered_rect(f.area(), 60, 7);
+        if popup.width >= 3 && popup.height >= 3 {
+            f.render_widget(Clear, popup);
+            let name = &prompt.names[prompt.values.len().min(prompt.names.len() - 1)];
+            let lines = vec![
+                Line::styled(
+                    format!(
+                        "Value for {} ({} of {})",
+                        name,
+                        prompt.values.len() + 1,
+                        prompt.names.len()
+                    ),
+                    Style::default().fg(text_primary),
+                ),
+                Line::styled(format!("> {}▏", prompt.input), Style::default().fg(accent)),
+                Line::styled(
+                    "int: / real: prefix to convert  enter next  esc cancel",
+                    Style::default().fg(text_muted),
+                ),
+            ];
+            let paragraph = Paragraph::new(lines)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(" Bind parameters ")
+                        .border_style(Style::default().fg(accent)),
+                )
+                .wrap(Wrap { trim: false });
+            f.render_widget(paragraph, popup);
+        }
+    }
+
+    if let Some(popup_state) = &app.popup {
+        let popup = centered_rect(f.area(), 80, 24);
+        if popup.width >= 3 && popup.height >= 3 {
+            f.render_widget(Clear, popup);
+            let text: Vec<Line> = popup_state
+                .lines
+                .iter()
+                .map(|l| {
+                    let line = Line::from(l.as_str());
+                    match l.get(..2) {
+                        Some("+ ") if popup_state.diff => {
+                            line.style(Style::default().fg(insert_accent))
+                        },
+                        Some("- ") if popup_state.diff => {
+                            line.style(Style::default().fg(app.theme.error))
+                        },
+                        _ => line,
+                    }
+                })
+                .collect();
+            let paragraph = Paragraph::new(text)
+                .style(Style::default().fg(text_primary))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(popup_state.title.as_str())
+                        .border_style(Style::default().fg(accent)),
+                )
+                .scroll((
+                    popup_state.scroll.min(u16::MAX as usize) as u16,
+                    popup_state.hscroll.min(u16::MAX as usize) as u16,
+                ));
+            let paragraph =
+                if popup_state.wrap { paragraph.wrap(Wrap { trim: false }) } else { paragraph };
+            f.render_widget(paragraph, popup);
+        }
+    }
+}
+
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let popup_width = width.min(area.width.saturating_sub(2));
+    let popup_height = height.min(area.height.saturating_sub(2));
+    let popup_x = area.x + area.width.saturating_sub(popup_width) / 2;
+    let popup_y = area.y + area.height.saturating_sub(popup_height) / 2;
+    Rect::new(popup_x, popup_y, popup_width, popup_height)
 }
 
 async fn run_app(
@@ -1213,35 +5046,197 @@ async fn run_app(
     mut app: App,
 ) -> Result<()> {
     let mut event_reader = EventStream::new();
+    let mut tail_timer = None;
+    let mut timer_period = None;
 
     loop {
+        app.pause_refresh_if_edited();
+        // `R` turns the timer on and off; a new interval ticks straight away
+        if timer_period != app.tail_interval {
+            timer_period = app.tail_interval;
+            tail_timer = timer_period.map(|period| {
+                let mut timer = tokio::time::interval(period);
+                timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                timer
+            });
+        }
         terminal.draw(|f| ui(f, &mut app))?;
 
-        if let Some(Ok(event)) = event_reader.next().await {
+        let event = tokio::select! {
+            event = event_reader.next() => event,
+            query_event = query_event(&mut app.running) => {
+                match query_event {
+                    QueryEvent::Progress(line) => app.record_progress(line),
+                    QueryEvent::Done(outcome) => {
+                        let running = app.running.take().expect("query is running");
+                        if let Err(e) = app.finish_query(running, outcome) {
+                            app.status = format_user_error(&e);
+                        }
+                    },
+                }
+                continue;
+            },
+            _ = next_tick(&mut tail_timer) => {
+                if let Err(e) = app.refresh_tail().await {
+                    app.status = format_user_error(&e);
+                }
+                continue;
+            },
+        };
+        if let Some(Ok(event)) = event {
             match event {
                 Event::Key(key) => {
+                    // The connection is busy, so nothing else may run until the query ends
+                    if app.running.is_some() {
+                        if key.code == KeyCode::Esc {
+                            app.cancel_query();
+                        } else {
+                            app.status = String::from("Query running (esc to cancel)");
+                        }
+                        continue;
+                    }
+                    if app.confirm.is_some() {
+                        if let Err(e) = app.handle_confirm_key(key).await {
+                            app.status = format_user_error(&e);
+                        }
+                        continue;
+                    }
+                    if app.bind_prompt.is_some() {
+                        if let Err(e) = app.handle_bind_prompt_key(key).await {
+                            app.status = format_user_error(&e);
+                        }
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && app.history_picker.visible
+                    {
+                        app.handle_history_picker_key(key);
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && app.table_picker.visible
+                    {
+                        if app.handle_table_picker_key(key) {
+                            app.status = String::from("Running query...");
+                            if let Err(e) = app.execute_query().await {
+                                app.status = format_user_error(&e);
+                            }
+                        }
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && app.command_line.visible
+                    {
+                        if let Some(command) = app.handle_command_line_key(key)
+                            && let Err(e) = app.run_command(&command).await
+                        {
+                            app.status = format_user_error(&e);
+                        }
+                        continue;
+                    }
+                    if app.popup.is_some() {
+                        app.handle_popup_key(key);
+                        continue;
+                    }
+                    if app.settings.is_some() {
+                        if let Err(e) = app.handle_settings_key(key) {
+                            app.status = format_user_error(&e);
+                        }
+                        continue;
+                    }
                     if matches!(app.editor_state.mode, EditorMode::Insert)
                         && key.code == KeyCode::Char('q')
                         && key.modifiers.contains(KeyModifiers::CONTROL)
                     {
-                        app.save_current_query_on_exit();
-                        return Ok(());
+                        app.save_current_query_on_exit();
+                        app.save_layout_on_exit();
+                        return Ok(());
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && key.code == KeyCode::Char('q')
+                        && key.modifiers.is_empty()
+                    {
+                        app.save_current_query_on_exit();
+                        app.save_layout_on_exit();
+                        return Ok(());
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && key.code == KeyCode::Char(':')
+                    {
+                        app.open_command_line();
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && app.handle_focus_key(&key)
+                    {
+                        app.pending_g = false;
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && app.focus == Pane::Editor
+                    {
+                        if app.pending_g {
+                            app.pending_g = false;
+                            if key.code == KeyCode::Char('d') {
+                                if let Err(e) = app.open_definition_under_cursor().await {
+                                    app.status = format_user_error(&e);
+                                }
+                                continue;
+                            }
+                            // Not `gd`: let the editor see the `g` it was holding back
+                            app.event_handler.on_key_event(
+                                crossterm::event::KeyEvent::from(KeyCode::Char('g')),
+                                &mut app.editor_state,
+                            );
+                        } else if key.code == KeyCode::Char('g') && key.modifiers.is_empty() {
+                            app.pending_g = true;
+                            continue;
+                        }
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && key.code == KeyCode::Char('K')
+                    {
+                        app.describe_table_under_cursor();
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && key.code == KeyCode::Char('H')
+                    {
+                        app.open_history_picker();
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && key.code == KeyCode::Char('Q')
+                    {
+                        if let Err(e) = app.explain_query_plan().await {
+                            app.status = format_user_error(&e);
+                        }
+                        continue;
                     }
                     if matches!(app.editor_state.mode, EditorMode::Normal)
-                        && key.code == KeyCode::Char('q')
-                        && key.modifiers.is_empty()
+                        && app.focus == Pane::Results
+                        && matches!(key.code, KeyCode::Char('[' | ']'))
                     {
-                        app.save_current_query_on_exit();
-                        return Ok(());
+                        app.cycle_result_tab(key.code == KeyCode::Char(']'));
+                        continue;
                     }
                     if matches!(app.editor_state.mode, EditorMode::Normal)
-                        && app.table_picker.visible
+                        && key.code == KeyCode::Char('R')
                     {
-                        if app.handle_table_picker_key(key) {
-                            app.status = String::from("Running query...");
-                            if let Err(e) = app.execute_query().await {
-                                app.status = format_user_error(&e);
-                            }
+                        app.toggle_auto_refresh();
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && key.code == KeyCode::Char('P')
+                    {
+                        app.dry_run();
+                        continue;
+                    }
+                    if matches!(app.editor_state.mode, EditorMode::Normal)
+                        && key.code == KeyCode::Char('#')
+                    {
+                        if let Err(e) = app.count_current_query().await {
+                            app.status = format_user_error(&e);
                         }
                         continue;
                     }
@@ -1253,89 +5248,110 @@ async fn run_app(
                             app.status = format_user_error(&e);
                         }
                     } else if matches!(app.editor_state.mode, EditorMode::Normal)
-                        && !app.results.is_empty()
+                        && !app.tab().results.is_empty()
                     {
                         match key.code {
                             KeyCode::Up => {
-                                if app.focus == Pane::Results && app.current_row > 0 {
-                                    app.current_row -= 1;
-                                    if app.current_row < app.vertical_scroll {
-                                        app.vertical_scroll = app.current_row;
-                                    }
+                                if app.focus == Pane::Results {
+                                    app.select_prev_row();
                                 }
                             },
                             KeyCode::Down => {
-                                if app.focus == Pane::Results
-                                    && app.current_row + 1 < app.results.len()
-                                {
-                                    app.current_row += 1;
-                                    if app.current_row >= app.vertical_scroll + app.visible_rows {
-                                        app.vertical_scroll =
-                                            app.current_row - app.visible_rows + 1;
-                                    }
+                                if app.focus == Pane::Results {
+                                    app.select_next_row();
                                 }
                             },
                             KeyCode::Left => {
                                 if app.focus == Pane::Editor {
                                     app.history_prev();
-                                } else if app.focus == Pane::Results {
-                                    if app.horizontal_scroll > 0
-                                        && app.current_col == app.horizontal_scroll
-                                    {
-                                        app.horizontal_scroll -= 1;
-                                        if app.current_col > 0 {
-                                            app.current_col -= 1;
-                                        }
-                                    } else if app.current_col > app.horizontal_scroll {
-                                        app.current_col -= 1;
-                                    }
+                                } else {
+                                    app.select_prev_col();
                                 }
                             },
                             KeyCode::Right => {
                                 if app.focus == Pane::Editor {
                                     app.history_next();
-                                } else if app.focus == Pane::Results {
-                                    if app.current_col + 1
-                                        == app.horizontal_scroll + app.visible_cols
-                                        && app.horizontal_scroll + app.visible_cols
-                                            < app.headers.len()
-                                    {
-                                        app.horizontal_scroll += 1;
-                                    } else if app.current_col + 1 < app.headers.len() {
-                                        app.current_col += 1;
-                                    }
+                                } else {
+                                    app.select_next_col();
                                 }
                             },
+                            KeyCode::Char('k') if app.focus == Pane::Results => {
+                                app.select_prev_row();
+                            },
+                            KeyCode::Char('j') if app.focus == Pane::Results => {
+                                app.select_next_row();
+                            },
+                            KeyCode::Char('h') if app.focus == Pane::Results => {
+                                app.select_prev_col();
+                            },
+                            KeyCode::Char('l') if app.focus == Pane::Results => {
+                                app.select_next_col();
+                            },
                             KeyCode::Tab => {
                                 app.focus = match app.focus {
                                     Pane::Editor => Pane::Results,
                                     Pane::Results => Pane::Editor,
                                 };
                             },
-                            KeyCode::Char('h') => {
-                                if app.focus == Pane::Editor {
-                                    app.history_prev();
-                                } else {
-                                    app.event_handler.on_key_event(key, &mut app.editor_state);
-                                }
+                            KeyCode::Char('h') if app.focus == Pane::Editor => {
+                                app.history_prev();
                             },
-                            KeyCode::Char('l') => {
-                                if app.focus == Pane::Editor {
-                                    app.history_next();
-                                } else {
-                                    app.event_handler.on_key_event(key, &mut app.editor_state);
-                                }
+                            KeyCode::Char('l') if app.focus == Pane::Editor => {
+                                app.history_next();
                             },
-                            KeyCode::Char('n') => {
-                                if app.focus == Pane::Editor {
-                                    app.new_query();
-                                } else {
-                                    app.event_handler.on_key_event(key, &mut app.editor_state);
-                                }
+                            KeyCode::Char('n') if app.focus == Pane::Editor => {
+                                app.new_query();
                             },
                             KeyCode::Char('t') => {
                                 app.open_table_picker();
                             },
+                            KeyCode::Char('z') => {
+                                app.toggle_editor_collapsed();
+                            },
+                            KeyCode::Char('|') => app.toggle_split_orientation(),
+                            KeyCode::Char('+') => app.layout.resize_editor(1),
+                            KeyCode::Char('-') => app.layout.resize_editor(-1),
+                            KeyCode::Char('T') if app.focus == Pane::Results => {
+                                app.toggle_transpose();
+                            },
+                            KeyCode::Char('C') if app.focus == Pane::Results => {
+                                app.open_command_line_with("col ");
+                            },
+                            KeyCode::Char('/') if app.focus == Pane::Results => app.open_search(),
+                            KeyCode::Char('n') if app.focus == Pane::Results => {
+                                app.jump_to_match(app.tab().cursor(), true, false);
+                            },
+                            KeyCode::Char('N') if app.focus == Pane::Results => {
+                                app.jump_to_match(app.tab().cursor(), false, false);
+                            },
+                            KeyCode::Esc
+                                if app.focus == Pane::Results && app.tab().search.is_some() =>
+                            {
+                                app.clear_search();
+                            },
+                            KeyCode::Char('s') if app.focus == Pane::Results => {
+                                app.sort_by_current_col();
+                            },
+                            KeyCode::Char('x') if app.focus == Pane::Results => {
+                                app.toggle_expanded_row();
+                            },
+                            KeyCode::Char('v') if app.focus == Pane::Results => {
+                                app.open_cell_value();
+                            },
+                            KeyCode::Char('y') if app.focus == Pane::Results => {
+                                app.copy_selection(false);
+                            },
+                            KeyCode::Char('Y') if app.focus == Pane::Results => {
+                                app.copy_selection(true);
+                            },
+                            KeyCode::Char('e') if app.focus == Pane::Results => {
+                                if let Err(e) = app.export_results(ExportFormat::Csv, None) {
+                                    app.status = format_user_error(&e);
+                                }
+                            },
+                            KeyCode::Char('E') if app.focus == Pane::Results => {
+                                app.open_command_line_with("export ");
+                            },
                             _ => {
                                 app.event_handler.on_key_event(key, &mut app.editor_state);
                             },
@@ -1358,6 +5374,14 @@ async fn run_app(
                             app.new_query();
                         } else if key.code == KeyCode::Char('t') {
                             app.open_table_picker();
+                        } else if key.code == KeyCode::Char('z') {
+                            app.toggle_editor_collapsed();
+                        } else if key.code == KeyCode::Char('|') {
+                            app.toggle_split_orientation();
+                        } else if key.code == KeyCode::Char('+') {
+                            app.layout.resize_editor(1);
+                        } else if key.code == KeyCode::Char('-') {
+                            app.layout.resize_editor(-1);
                         } else {
                             app.event_handler.on_key_event(key, &mut app.editor_state);
                         }
@@ -1395,13 +5419,47 @@ async fn run_app(
                     app.event_handler.on_mouse_event(mouse_event, &mut app.editor_state);
                     app.update_autocomplete();
                 },
-                Event::Resize(_, _) => {},
+                Event::Resize(width, height) => {
+                    app.fit_viewport(Rect::new(0, 0, width, height));
+                    // Repaint everything rather than diffing against the old-size buffer
+                    terminal.clear()?;
+                },
                 _ => {},
             }
         }
     }
 }
 
+/// Waits for the next tick, or forever when there is no timer.
+async fn next_tick(timer: &mut Option<tokio::time::Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        },
+        None => std::future::pending().await,
+    }
+}
+
+/// What `query_event` saw happen to the running query.
+enum QueryEvent {
+    /// A statement of the script finished, with its `:log` line.
+    Progress(String),
+    Done(Result<Result<RunOutcome>, tokio::task::JoinError>),
+}
+
+/// Resolves with the running query's next log line, or once its task ends (after every line has
+/// been delivered); never while nothing is running.
+async fn query_event(running: &mut Option<RunningQuery>) -> QueryEvent {
+    let Some(running) = running else {
+        return std::future::pending().await;
+    };
+    tokio::select! {
+        biased;
+        Some(line) = running.log.recv() => QueryEvent::Progress(line),
+        outcome = &mut running.task => QueryEvent::Done(outcome),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -1412,7 +5470,9 @@ async fn main() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let app = App::new(&cli.database).context("Failed to initialize app")?;
+    let mut app = App::new(&cli).context("Failed to initialize app")?;
+    let size = terminal.size()?;
+    app.fit_viewport(Rect::new(0, 0, size.width, size.height));
 
     let res = run_app(&mut terminal, app).await;
 
@@ -1424,79 +5484,850 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        fs,
-        time::{SystemTime, UNIX_EPOCH},
-    };
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use super::*;
+
+    fn unique_temp_path(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock should be after unix epoch")
+            .as_nanos();
+        env::temp_dir().join(format!("squeal-test-{}-{}-{}", name, std::process::id(), nanos))
+    }
+
+    /// Draws `app` on a `width` x `height` test terminal, for checks on cell styles.
+    fn render_buffer(app: &mut App, width: u16, height: u16) -> ratatui::buffer::Buffer {
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(width, height))
+            .expect("test terminal should build");
+        terminal.draw(|f| ui(f, app)).expect("ui should render");
+        terminal.backend().buffer().clone()
+    }
+
+    /// Draws `app` on a `width` x `height` test terminal and returns the screen text, one line per
+    /// terminal row.
+    fn render_screen(app: &mut App, width: u16, height: u16) -> String {
+        let buffer = render_buffer(app, width, height);
+        let rows: Vec<String> =
+            (0..height).map(|y| (0..width).map(|x| buffer[(x, y)].symbol()).collect()).collect();
+        rows.join("\n")
+    }
+
+    fn test_app_with_schema(schema: Schema) -> App {
+        let mut editor_state = EditorState::default();
+        editor_state.mode = EditorMode::Insert;
+        App {
+            editor_state,
+            event_handler: EditorEventHandler::default(),
+            database_path: "/tmp/test.db".to_string(),
+            conn: Arc::new(Mutex::new(
+                Connection::open_in_memory().expect("in-memory database should open"),
+            )),
+            sandbox_origin: None,
+            compare: None,
+            expanded_row: false,
+            sort_settings: SortSettings::default(),
+            result_tabs: vec![ResultTab::default()],
+            active_tab: 0,
+            max_rows: DEFAULT_MAX_ROWS,
+            script_log: Vec::new(),
+            last_statements: None,
+            last_params: Vec::new(),
+            bind_prompt: None,
+            running: None,
+            query_file: None,
+            saved_query: None,
+            queries_in_background: false,
+            tail_interval: None,
+            refresh_period: Duration::from_secs(DEFAULT_REFRESH_SECS),
+            refresh_query: None,
+            status: "ready".to_string(),
+            visible_rows: 10,
+            visible_cols: 5,
+            autocomplete: AutocompleteState {
+                suggestions: Vec::new(),
+                selected: 0,
+                visible: false,
+            },
+            schema,
+            focus: Pane::Editor,
+            query_history: Vec::new(),
+            history_index: None,
+            history_draft: None,
+            history_path: Some(unique_temp_path("history")),
+            table_picker: PickerState { visible: false, filter: String::new(), selected: 0 },
+            history_picker: PickerState { visible: false, filter: String::new(), selected: 0 },
+            command_line: CommandLineState { visible: false, input: String::new(), prompt: ':' },
+            editor_collapsed: false,
+            layout: LayoutSettings::default(),
+            table_style: TableStyle { zebra: true, row_separators: false },
+            autocomplete_limits: AutocompleteLimits::default(),
+            popup: None,
+            clipboard: None,
+            config: Config::default(),
+            config_path: unique_temp_path("config"),
+            config_load_failed: false,
+            settings: None,
+            confirm: None,
+            focus_keys: FocusKeys::default(),
+            pending_g: false,
+            trailing_semicolon: true,
+            keyword_case: KeywordCase::Match,
+            read_only: false,
+            attachments: Vec::new(),
+            theme: Theme::default(),
+        }
+    }
+
+    fn text(s: &str) -> CellValue {
+        CellValue::Text(s.to_string())
+    }
+
+    fn empty_schema() -> Schema {
+        Schema::new(vec![], std::collections::HashMap::new())
+    }
+
+    fn columns(names: &[&str]) -> Vec<ColumnInfo> {
+        names
+            .iter()
+            .map(|name| ColumnInfo {
+                name: name.to_string(),
+                decl_type: String::new(),
+                not_null: false,
+                default: None,
+                pk: 0,
+            })
+            .collect()
+    }
+
+    fn column_names(schema: &Schema, table: &str) -> Vec<String> {
+        schema.columns_by_table[table].iter().map(|c| c.name.clone()).collect()
+    }
+
+    #[test]
+    fn schema_completions_are_sorted_deduped_and_filtered() {
+        let mut columns_by_table = std::collections::HashMap::new();
+        columns_by_table.insert("users".to_string(), columns(&["name", "id"]));
+        columns_by_table.insert("orders".to_string(), columns(&["id", "note"]));
+        let schema = Schema::new(vec!["users".to_string(), "orders".to_string()], columns_by_table);
+
+        assert_eq!(schema.tables, vec!["orders".to_string(), "users".to_string()]);
+        assert_eq!(column_names(&schema, "users"), ["name", "id"]);
+        let all: Vec<&String> = schema.column_completions.matching("").collect();
+        assert_eq!(all, ["id", "name", "note"]);
+        let n: Vec<&String> = schema.column_completions.matching("N").collect();
+        assert_eq!(n, ["name", "note"]);
+        let users: Vec<&String> =
+            schema.column_completions_by_table["users"].matching("I").collect();
+        assert_eq!(users, ["id"]);
+    }
+
+    #[test]
+    fn referenced_tables_reads_from_and_join_clauses() {
+        let refs = |sql| {
+            referenced_tables(sql)
+                .into_iter()
+                .map(|t| (t.name, t.alias))
+                .collect::<Vec<(String, Option<String>)>>()
+        };
+        let named = |name: &str, alias: Option<&str>| (name.to_string(), alias.map(String::from));
+        assert_eq!(
+            refs("select * from users u join \"Order Items\" as oi on oi.user_id = u.id"),
+            [named("users", Some("u")), named("Order Items", Some("oi"))]
+        );
+        assert_eq!(
+            refs("select 1 from aux1.orders, users where x = 'from nowhere' -- join junk"),
+            [named("aux1.orders", None), named("users", None)]
+        );
+        assert_eq!(
+            refs("select * from (select id from teams) t left join users"),
+            [named("teams", None), named("users", None)]
+        );
+        assert_eq!(
+            refs("select 'it''s from me' from \"odd \"\"name\"\"\" o join t"),
+            [named("odd \"name\"", Some("o")), named("t", None)]
+        );
+        assert!(refs("select 1").is_empty());
+    }
+
+    #[test]
+    fn one_scanner_handles_escaped_quotes_and_comments() {
+        let sql = "select 'a;''b' -- c;\n; /* d; */ select \"e;\"\"\" from [f;g];";
+        assert_eq!(
+            split_statements(sql),
+            ["select 'a;''b' -- c;", "/* d; */ select \"e;\"\"\" from [f;g]"]
+        );
+        assert_eq!(uppercase_words("select 'from x' -- limit\n from t"), ["SELECT", "FROM", "T"]);
+        assert_eq!(statement_label("/* note */ create table t (x)"), "CREATE TABLE");
+    }
+
+    #[test]
+    fn column_completion_stays_within_the_statement_tables() {
+        let mut columns_by_table = std::collections::HashMap::new();
+        columns_by_table.insert("users".to_string(), columns(&["id", "name"]));
+        columns_by_table.insert("orders".to_string(), columns(&["id", "note", "user_id"]));
+        columns_by_table.insert("teams".to_string(), columns(&["nickname"]));
+        let mut app = test_app_with_schema(Schema::new(
+            vec!["users".to_string(), "orders".to_string(), "teams".to_string()],
+            columns_by_table,
+        ));
+        let suggest = |app: &mut App, before: &str, after: &str| {
+            app.set_query(&format!("{}{}", before, after));
+            app.editor_state.cursor.col = before.chars().count();
+            app.update_autocomplete();
+            app.autocomplete.suggestions.clone()
+        };
+        assert_eq!(suggest(&mut app, "select n", " from users"), ["name"]);
+        assert_eq!(suggest(&mut app, "select n", " from users join orders o"), ["name", "note"]);
+        assert_eq!(
+            suggest(&mut app, "select o.", " from users u join orders o"),
+            ["id", "note", "user_id"]
+        );
+        assert_eq!(suggest(&mut app, "select n", ""), ["name", "nickname", "note"]);
+        assert_eq!(suggest(&mut app, "select 1; select n", " from teams"), ["nickname"]);
+        // A `;` in a string or comment doesn't start a new statement
+        assert_eq!(suggest(&mut app, "select ';' from t", ""), ["teams"]);
+        assert_eq!(suggest(&mut app, "select 1 /* ; */ from t", ""), ["teams"]);
+    }
+
+    #[test]
+    fn completion_kind_context_rules() {
+        assert_eq!(completion_kind("select "), CompletionKind::Column);
+        assert_eq!(completion_kind("select id from "), CompletionKind::Table);
+        assert_eq!(completion_kind("select * from users join "), CompletionKind::Table);
+        assert_eq!(completion_kind("select * from users on "), CompletionKind::Column);
+        assert_eq!(completion_kind("select * from users where "), CompletionKind::Keyword);
+    }
+
+    #[test]
+    fn truncate_helpers_are_unicode_safe() {
+        assert_eq!(truncate_left("abcdef", 4), "…def");
+        assert_eq!(truncate_right("abcdef", 4), "abc…");
+        assert_eq!(truncate_left("猫犬鳥", 2), "…鳥");
+        assert_eq!(truncate_right("猫犬鳥", 2), "猫…");
+    }
+
+    #[test]
+    fn autocomplete_handles_multibyte_text_around_the_cursor() {
+        assert_eq!(identifier_start("select '→'||naï"), "select '→'||".len());
+        assert_eq!(qualifier_before_word("x→café.nü", "x→café.".len()), Some("café".to_string()));
+
+        let mut columns_by_table = std::collections::HashMap::new();
+        columns_by_table.insert("café".to_string(), columns(&["naïve", "prix"]));
+        let mut app = test_app_with_schema(Schema::new(vec!["café".to_string()], columns_by_table));
+
+        app.set_query("select '🙂'→naï");
+        app.update_autocomplete();
+        assert_eq!(app.autocomplete.suggestions, ["naïve"]);
+        app.accept_autocomplete();
+        assert_eq!(app.current_query(), "select '🙂'→naïve");
+
+        app.set_query("select café.p");
+        app.update_autocomplete();
+        assert_eq!(app.autocomplete.suggestions, ["prix"]);
+        app.accept_autocomplete();
+        assert_eq!(app.current_query(), "select café.prix");
+    }
+
+    #[test]
+    fn keyword_completion_follows_the_typed_case() {
+        let mut app = test_app_with_schema(empty_schema());
+        for (typed, inserted) in
+            [("sel", "select"), ("Sel", "Select"), ("SEL", "SELECT"), ("sEl", "SELECT")]
+        {
+            app.set_query(typed);
+            app.update_autocomplete();
+            assert_eq!(app.autocomplete.suggestions[0], inserted);
+            app.accept_autocomplete();
+            assert_eq!(app.current_query(), inserted);
+        }
+        assert_eq!(KeywordCase::Match.apply("GROUP BY", "Gr"), "Group By");
+        assert_eq!(KeywordCase::Match.apply("SELECT", "S"), "SELECT");
+        assert_eq!(KeywordCase::Match.apply("SELECT", "s"), "select");
+        assert_eq!(KeywordCase::Match.apply("GROUP BY", "gr"), "group by");
+
+        app.keyword_case = KeywordCase::Upper;
+        app.set_query("sel");
+        app.update_autocomplete();
+        assert_eq!(app.autocomplete.suggestions[0], "SELECT");
+
+        let mut config = Config::default();
+        config.set("autocomplete", "keyword_case", Value::String("lower".to_string()));
+        assert_eq!(KeywordCase::from_config(&config), (KeywordCase::Lower, Vec::new()));
+        config.set("autocomplete", "keyword_case", Value::String("title".to_string()));
+        let (case, warnings) = KeywordCase::from_config(&config);
+        assert_eq!((case, warnings.len()), (KeywordCase::Match, 1));
+    }
+
+    #[test]
+    fn prefix_at_char_respects_char_boundaries() {
+        let s = "a猫b";
+        assert_eq!(prefix_at_char(s, 0), "");
+        assert_eq!(prefix_at_char(s, 1), "a");
+        assert_eq!(prefix_at_char(s, 2), "a猫");
+        assert_eq!(prefix_at_char(s, 3), "a猫b");
+        assert_eq!(prefix_at_char(s, 10), "a猫b");
+    }
+
+    #[test]
+    fn word_at_char_finds_identifier_around_cursor() {
+        let line = "select * from émployés e";
+        assert_eq!(word_at_char(line, 14), Some("émployés"));
+        assert_eq!(word_at_char(line, 18), Some("émployés"));
+        assert_eq!(word_at_char(line, 22), Some("émployés"));
+        assert_eq!(word_at_char(line, 7), None);
+        assert_eq!(word_at_char(line, 24), Some("e"));
+        assert_eq!(word_at_char("", 0), None);
+        assert_eq!(qualified_name_at_char("from aux1.orders", 6), Some((Some("aux1"), "orders")));
+        assert_eq!(qualified_name_at_char("from aux1.orders", 16), Some((Some("aux1"), "orders")));
+        assert_eq!(qualified_name_at_char("from users.", 8), Some((None, "users")));
+        assert_eq!(qualified_name_at_char("a . b", 2), None);
+    }
+
+    #[tokio::test]
+    async fn goto_definition_shows_create_statement() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("create table users (id integer primary key, name text);");
+        app.execute_query().await.expect("setup should run");
+
+        app.set_query("select * from Users");
+        app.editor_state.cursor.col = 16;
+        app.open_definition_under_cursor().await.expect("definition lookup should run");
+        let popup = app.popup.as_ref().expect("definition popup should open");
+        assert_eq!(popup.title, " table users ");
+        assert_eq!(popup.lines[0], "CREATE TABLE users (id integer primary key, name text)");
+        assert!(popup.lines.iter().any(|l| l == "  name"));
+
+        app.popup = None;
+        app.set_query("select nothing");
+        app.open_definition_under_cursor().await.expect("definition lookup should run");
+        assert!(app.popup.is_none());
+        assert_eq!(app.status, "No table or view named nothing");
+
+        lock_connection(&app.conn)
+            .expect("connection should lock")
+            .execute_batch("attach ':memory:' as aux; create table aux.orders (id, total);")
+            .expect("attach should run");
+        app.set_query("select * from aux.orders join users");
+        for col in [15, 19, 24] {
+            app.editor_state.cursor.col = col;
+            app.open_definition_under_cursor().await.expect("definition lookup should run");
+            let popup = app.popup.take().expect("definition popup should open");
+            assert_eq!(popup.title, " table aux.orders ", "cursor at {}", col);
+            assert!(popup.lines.iter().any(|l| l == "  total"));
+        }
+        app.set_query("select * from orders o join main.users u");
+        app.editor_state.cursor.col = 15;
+        app.open_definition_under_cursor().await.expect("definition lookup should run");
+        assert_eq!(app.popup.take().map(|p| p.title), Some(" table aux.orders ".to_string()));
+        app.editor_state.cursor.col = 35;
+        app.open_definition_under_cursor().await.expect("definition lookup should run");
+        assert_eq!(app.popup.take().map(|p| p.title), Some(" table users ".to_string()));
+        app.set_query("select main.orders");
+        app.open_definition_under_cursor().await.expect("definition lookup should run");
+        assert_eq!(app.status, "No table or view named main.orders");
+    }
+
+    #[tokio::test]
+    async fn describe_lists_types_keys_and_nullability() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query(
+            "create table line_items (order_id integer not null, sku text, qty int default 1, \
+             primary key (order_id, sku));",
+        );
+        app.execute_query().await.expect("setup should run");
+        app.schema = App::load_schema(&lock_connection(&app.conn).expect("connection should lock"))
+            .expect("schema should load");
+
+        app.editor_state.mode = EditorMode::Normal;
+        app.open_table_picker();
+        app.handle_table_picker_key(crossterm::event::KeyEvent::from(KeyCode::Tab));
+        assert!(!app.table_picker.visible);
+        let popup = app.popup.as_mut().expect("describe popup should open");
+        assert_eq!(
+            popup.lines,
+            [
+                "column    type     not null  default  pk",
+                "order_id  INTEGER  yes                1",
+                "sku       TEXT                        2",
+                "qty       INT                1",
+            ]
+        );
+        assert!(!popup.wrap);
+        app.handle_popup_key(crossterm::event::KeyEvent::from(KeyCode::Char('l')));
+        assert_eq!(app.popup.as_ref().map(|p| p.hscroll), Some(4));
+
+        app.run_command("describe nope").await.expect("command should run");
+        assert_eq!(app.status, "No such table: nope");
+    }
+
+    #[test]
+    fn settings_modal_changes_and_persists_pragmas() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.open_settings().expect("settings should open");
+        let foreign_keys = |app: &App| app.settings.as_ref().map(|s| s.values[0].clone());
+        let initial = foreign_keys(&app);
+
+        app.handle_settings_key(crossterm::event::KeyEvent::from(KeyCode::Enter))
+            .expect("toggle should apply");
+        let toggled = foreign_keys(&app).expect("settings should stay open");
+        assert_ne!(Some(toggled.clone()), initial);
+
+        app.handle_settings_key(crossterm::event::KeyEvent::from(KeyCode::Char('s')))
+            .expect("save should succeed");
+        let saved = Config::load(&app.config_path).expect("saved config should load");
+        assert_eq!(saved.get("pragmas", "foreign_keys"), Some(&Value::String(toggled)));
+        let _ = fs::remove_file(&app.config_path);
+    }
+
+    #[test]
+    fn settings_refuse_to_overwrite_config_that_failed_to_load() {
+        let mut app = test_app_with_schema(empty_schema());
+        fs::write(&app.config_path, "not toml\n").expect("config should be written");
+        app.config_load_failed = true;
+        app.open_settings().expect("settings should open");
+        app.handle_settings_key(crossterm::event::KeyEvent::from(KeyCode::Enter))
+            .expect("toggle should apply");
+        let err = app
+            .handle_settings_key(crossterm::event::KeyEvent::from(KeyCode::Char('s')))
+            .expect_err("save should be refused");
+        assert!(err.to_string().starts_with("Not saving:"));
+        assert_eq!(fs::read_to_string(&app.config_path).ok().as_deref(), Some("not toml\n"));
+        let _ = fs::remove_file(&app.config_path);
+    }
+
+    #[test]
+    fn directional_focus_keys_target_a_pane() {
+        use crossterm::event::KeyEvent;
+
+        let mut app = test_app_with_schema(empty_schema());
+        assert!(app.handle_focus_key(&KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL)));
+        assert_eq!(app.focus, Pane::Results);
+        assert!(app.handle_focus_key(&KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL)));
+        assert_eq!(app.focus, Pane::Results);
+        assert!(app.handle_focus_key(&KeyEvent::new(KeyCode::Char('k'), KeyModifiers::CONTROL)));
+        assert_eq!(app.focus, Pane::Editor);
+        assert!(!app.handle_focus_key(&KeyEvent::from(KeyCode::Char('l'))));
+    }
+
+    #[test]
+    fn detects_recursive_ctes_without_limit() {
+        let unbounded =
+            "with recursive n(x) as (select 1 union all select x + 1 from n) select x from n";
+        assert!(is_unbounded_recursive_cte(unbounded));
+        assert!(!is_unbounded_recursive_cte(&format!("{} limit 5", unbounded)));
+        assert!(!is_unbounded_recursive_cte("with n(x) as (select 1) select x from n"));
+        assert!(!is_unbounded_recursive_cte(
+            "with recursive n(x) as (select 1) delete from t where id in n"
+        ));
+
+        let commented = cap_recursive_cte(format!("{} -- count up", unbounded));
+        let conn = Connection::open_in_memory().expect("in-memory db should open");
+        let rows: i64 = conn
+            .query_row(&format!("select count(*) from ({})", commented), [], |row| row.get(0))
+            .expect("capped query should run");
+        assert_eq!(rows, RECURSIVE_CTE_ROW_CAP as i64);
+    }
+
+    #[tokio::test]
+    async fn recursive_cte_prompt_can_cap_rows() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query(
+            "with recursive n(x) as (select 1 union all select x + 1 from n) select x from n;",
+        );
+        app.execute_query().await.expect("query should wait for confirmation");
+        assert!(app.confirm.is_some());
+        assert!(app.tab().results.is_empty());
+
+        app.handle_confirm_key(crossterm::event::KeyEvent::from(KeyCode::Char('x')))
+            .await
+            .expect("unrelated key should be ignored");
+        assert!(app.confirm.is_some());
+
+        app.handle_confirm_key(crossterm::event::KeyEvent::from(KeyCode::Char('y')))
+            .await
+            .expect("capped query should run");
+        assert!(app.confirm.is_none());
+        assert_eq!(app.tab().results.len(), RECURSIVE_CTE_ROW_CAP);
+        assert_eq!(app.tab().row_limit, Some(RECURSIVE_CTE_ROW_CAP));
+        assert_eq!(app.status, "Results limited to 1,000 rows (query had no LIMIT)");
+
+        let screen = render_screen(&mut app, 60, 24);
+        assert!(screen.contains("Results [limited to 1,000]"), "{screen}");
+
+        app.set_query("select 1;");
+        app.execute_query().await.expect("query should run");
+        assert!(app.tab().row_limit.is_none());
+    }
+
+    #[tokio::test]
+    async fn max_rows_stops_fetching_and_says_so() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.max_rows = 10;
+        app.set_query(
+            "with recursive n(x) as (select 1 union all select x + 1 from n limit 50) select x \
+             from n;",
+        );
+        app.execute_query().await.expect("query should run");
+        assert_eq!(app.tab().results.len(), 10);
+        assert_eq!(app.tab().row_limit, Some(10));
+        assert!(app.status.starts_with("Showing first 10 of more rows in "), "{}", app.status);
+        assert!(app.status.ends_with("(query was not LIMITed)"), "{}", app.status);
+
+        app.set_query("select 1 union all select 2;");
+        app.execute_query().await.expect("query should run");
+        assert_eq!(app.tab().results.len(), 2);
+        assert!(app.tab().row_limit.is_none());
+    }
+
+    #[tokio::test]
+    async fn query_files_save_and_load() {
+        let mut app = test_app_with_schema(empty_schema());
+        let path = unique_temp_path("query.sql");
+        let name = path.display().to_string();
+        app.set_query("select 1;\nselect 'é';");
+        app.run_command(&format!("w {}", name)).await.expect("save should succeed");
+        assert_eq!(app.status, format!("Wrote 22 bytes to {}", name));
+        assert_eq!(fs::read_to_string(&path).expect("file should exist"), "select 1;\nselect 'é';");
+
+        fs::write(&path, "select 2;\n").expect("file should be written");
+        app.run_command(&format!("e {}", name)).await.expect("load should succeed");
+        assert_eq!(app.current_query(), "select 2;");
+        assert_eq!((app.editor_state.cursor.row, app.editor_state.cursor.col), (0, 9));
+        app.set_query("select 3;");
+        app.run_command("w").await.expect("bare :w should reuse the path");
+        assert_eq!(fs::read_to_string(&path).expect("file should exist"), "select 3;");
+
+        fs::write(&path, [0xff, 0xfe]).expect("file should be written");
+        let err = app.run_command(&format!("e {}", name)).await.expect_err("not UTF-8");
+        assert!(format_user_error(&err).contains(&name), "{}", format_user_error(&err));
+        assert_eq!(app.current_query(), "select 3;");
+        fs::remove_file(&path).expect("file should be removed");
+        assert!(app.run_command(&format!("e {}", name)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn edit_refuses_to_drop_unsaved_text_unless_forced() {
+        let mut app = test_app_with_schema(empty_schema());
+        let path = unique_temp_path("edit.sql");
+        let name = path.display().to_string();
+        fs::write(&path, "select 2;\n").expect("file should be written");
+        app.set_query("select 1;");
+        app.run_command(&format!("e {}", name)).await.expect("refusal is not an error");
+        assert_eq!(app.status, "Unsaved changes (:w to save, :e! to discard)");
+        assert_eq!(app.current_query(), "select 1;");
+
+        app.query_history.push(String::from("select 1;"));
+        app.run_command(&format!("e {}", name)).await.expect("history keeps the text");
+        assert_eq!(app.current_query(), "select 2;");
+        app.set_query("select 4;");
+        app.run_command(&format!("e! {}", name)).await.expect("forced load");
+        assert_eq!(app.current_query(), "select 2;");
+        app.run_command(&format!("e {}", name)).await.expect("unchanged since load");
+        assert_eq!(app.status, format!("Loaded {}", name));
+        fs::remove_file(&path).expect("file should be removed");
+    }
+
+    #[tokio::test]
+    async fn each_run_opens_a_result_tab_that_keeps_its_cursor() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("select 1 as a, 2 as b union all select 3, 4;");
+        app.execute_query().await.expect("query should run");
+        assert_eq!(app.result_tabs.len(), 1, "the first run fills the empty tab");
+        app.select_next_row();
+        app.select_next_col();
+        app.tab_mut().search = Some(SearchState { term: "4".to_string(), origin: (0, 0) });
+        app.set_query("select 'x' as only;");
+        app.execute_query().await.expect("query should run");
+        assert_eq!((app.result_tabs.len(), app.active_tab), (2, 1));
+        assert_eq!(app.tab().headers, ["only"]);
+        assert!(app.tab().search.is_none());
+
+        app.set_query("create table t (x);");
+        app.execute_query().await.expect("create should run");
+        assert_eq!(app.result_tabs.len(), 2, "runs without columns don't open a tab");
+        assert_eq!(app.tab().headers, ["only"]);
+
+        app.cycle_result_tab(true);
+        assert_eq!(app.active_tab, 0);
+        assert_eq!(app.tab().headers, ["a", "b"]);
+        assert_eq!(app.tab().cursor(), (1, 1));
+        assert!(app.tab().search.is_some(), "marks stay with their tab");
+        assert_eq!(app.status, "Tab 1 of 2: 2 rows");
+
+        let screen = render_screen(&mut app, 80, 20);
+        assert!(screen.contains("2 select 'x' as only"), "{screen}");
+
+        app.cycle_result_tab(false);
+        assert_eq!((app.active_tab, app.tab().current_row), (1, 0));
+        for i in 0..MAX_RESULT_TABS {
+            app.set_query(&format!("select {};", i));
+            app.execute_query().await.expect("query should run");
+        }
+        assert_eq!(app.result_tabs.len(), MAX_RESULT_TABS);
+        assert_eq!(app.result_tabs[0].sql, "select 0");
+        assert_eq!(app.active_tab, MAX_RESULT_TABS - 1);
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_a_running_query() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.queries_in_background = true;
+        app.set_query("select 1 as kept;");
+        app.execute_query().await.expect("query should start");
+        app.wait_for_query().await.expect("query should finish");
+
+        app.set_query(
+            "with recursive n(x) as (select 1 union all select x + 1 from n limit 1000000000) \
+             select count(*) from n;",
+        );
+        app.execute_query().await.expect("query should start");
+        assert!(app.running.is_some());
+        assert_eq!(app.status, "Running query... (esc to cancel)");
+        while !app.running.as_ref().expect("query is running").task.is_finished() {
+            app.cancel_query();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        app.wait_for_query().await.expect("cancelling is not an error");
+        assert!(app.running.is_none());
+        assert_eq!(app.status, "Query cancelled");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(1)]]);
+    }
+
+    #[tokio::test]
+    async fn placeholders_prompt_for_values_before_running() {
+        let mut app = test_app_with_schema(empty_schema());
+        let key = |code| crossterm::event::KeyEvent::from(code);
+        let type_text = |app: &mut App, text: &str| {
+            for ch in text.chars() {
+                app.bind_prompt.as_mut().expect("prompt should be open").input.push(ch);
+            }
+        };
+
+        app.set_query("select ?, :name, ?1 || 'x';");
+        app.execute_query().await.expect("query should wait for values");
+        assert!(app.tab().results.is_empty());
+        assert_eq!(
+            app.bind_prompt.as_ref().map(|p| p.names.clone()),
+            Some(vec!["?1".to_string(), ":name".to_string()])
+        );
+
+        type_text(&mut app, "int:x");
+        app.handle_bind_prompt_key(key(KeyCode::Enter)).await.expect("key handled");
+        assert_eq!(app.status, "Not an integer: x");
+        app.bind_prompt.as_mut().expect("prompt should stay open").input.clear();
+        type_text(&mut app, "int: 5");
+        app.handle_bind_prompt_key(key(KeyCode::Enter)).await.expect("key handled");
+        type_text(&mut app, "O'Brien");
+        app.handle_bind_prompt_key(key(KeyCode::Enter)).await.expect("query should run");
+        assert!(app.bind_prompt.is_none());
+        assert_eq!(
+            app.tab().results,
+            vec![vec![CellValue::Integer(5), text("O'Brien"), text("5x")]]
+        );
+
+        app.tab_mut().results.clear();
+        app.execute_query().await.expect("query should wait for values");
+        app.handle_bind_prompt_key(key(KeyCode::Esc)).await.expect("key handled");
+        assert!(app.bind_prompt.is_none());
+        assert!(app.tab().results.is_empty());
+        assert_eq!(app.status, "Cancelled: query not run");
+    }
+
+    #[test]
+    fn splits_only_on_top_level_semicolons() {
+        assert_eq!(
+            split_statements(
+                "insert into t values ('a;b', 'it''s; fine');\n;;\nselect \"x;y\", [p;q] from t \
+                 -- done; really\n/* skip; this */ ;"
+            ),
+            vec![
+                "insert into t values ('a;b', 'it''s; fine')".to_string(),
+                "select \"x;y\", [p;q] from t -- done; really\n/* skip; this */".to_string(),
+            ]
+        );
+        assert_eq!(split_statements("select 1; -- trailing note"), vec!["select 1".to_string()]);
+        assert_eq!(split_statements("select 1; /* unterminated ;"), vec!["select 1".to_string()]);
+        assert!(split_statements(" ; -- nothing\n;").is_empty());
+    }
+
+    #[tokio::test]
+    async fn writes_report_rows_affected_and_time() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("create table t(x)");
+        app.execute_query().await.expect("create should run");
+        assert!(app.status.starts_with("CREATE TABLE done in "), "{}", app.status);
+
+        app.set_query("insert into t values (1), (2), (3)");
+        app.execute_query().await.expect("insert should run");
+        assert!(app.status.starts_with("Inserted 3 rows in "), "{}", app.status);
+
+        app.set_query("update t set x = x + 1 where x > 1");
+        app.execute_query().await.expect("update should run");
+        assert!(app.status.starts_with("Updated 2 rows in "), "{}", app.status);
+        assert!(app.status.ends_with("ms"), "{}", app.status);
+
+        app.set_query("delete from t where x > 100");
+        app.execute_query().await.expect("delete should run");
+        assert!(app.status.starts_with("Deleted 0 rows in "), "{}", app.status);
+
+        assert_eq!(format_elapsed(Duration::from_micros(3100)), "3.1ms");
+        assert_eq!(format_elapsed(Duration::from_millis(2500)), "2.50s");
+    }
+
+    #[tokio::test]
+    async fn semicolon_in_string_runs_as_one_statement() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("create table t(v); insert into t values ('a;b'); select v from t;");
+        app.execute_query().await.expect("script should run");
+        assert_eq!(app.tab().results, vec![vec![text("a;b")]]);
+    }
 
-    use super::*;
+    #[tokio::test]
+    async fn query_plan_explains_statement_under_cursor() {
+        let mut app = test_app_with_schema(empty_schema());
+        lock_connection(&app.conn)
+            .expect("connection should lock")
+            .execute_batch("create table t(x); create table u(y);")
+            .expect("setup should run");
+        app.set_query("select 1;\nselect * from t where x in (select y from u);\nselect 2;");
+        app.tab_mut().results = vec![vec![text("kept")]];
+        app.editor_state.cursor = edtui::Index2::new(1, 4);
+
+        app.explain_query_plan().await.expect("plan should run");
+        let popup = app.popup.as_ref().expect("plan should open a popup");
+        assert_eq!(popup.title, " Query plan ");
+        assert!(popup.lines[0].starts_with("├─ SCAN t"), "{:?}", popup.lines);
+        assert!(popup.lines.iter().any(|l| l.starts_with("└─ LIST SUBQUERY")), "{:?}", popup.lines);
+        assert!(popup.lines.iter().any(|l| l.starts_with("   ├─ SCAN u")), "{:?}", popup.lines);
+        assert_eq!(
+            popup.lines.last().map(String::as_str),
+            Some("-- select * from t where x in (select y from u)")
+        );
+        assert_eq!(app.tab().results, vec![vec![text("kept")]]);
+    }
 
-    fn unique_temp_path(name: &str) -> PathBuf {
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("clock should be after unix epoch")
-            .as_nanos();
-        env::temp_dir().join(format!("squeal-test-{}-{}-{}", name, std::process::id(), nanos))
+    #[test]
+    fn editor_border_shows_cursor_line_column_and_statement() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("select ';';\nselect 2;\nselect 3");
+        app.editor_state.cursor.row = 1;
+        app.editor_state.cursor.col = 3;
+        assert_eq!(app.cursor_position_label(), "Ln 2, Col 4 · stmt 2/3");
+        app.editor_state.cursor.row = 0;
+        app.editor_state.cursor.col = 9;
+        assert_eq!(app.cursor_position_label(), "Ln 1, Col 10 · stmt 1/3");
+        app.set_query("");
+        assert_eq!(app.cursor_position_label(), "Ln 1, Col 1");
+
+        app.set_query("select 1;\nselect 2;");
+        let screen = render_screen(&mut app, 60, 20);
+        assert!(screen.contains(" Ln 2, Col 10 · stmt 2/2 "), "{screen}");
     }
 
-    fn test_app_with_schema(schema: Schema) -> App {
-        let mut editor_state = EditorState::default();
-        editor_state.mode = EditorMode::Insert;
-        App {
-            editor_state,
-            event_handler: EditorEventHandler::default(),
-            database_path: "/tmp/test.db".to_string(),
-            results: Vec::new(),
-            headers: Vec::new(),
-            status: "ready".to_string(),
-            current_row: 0,
-            current_col: 0,
-            vertical_scroll: 0,
-            horizontal_scroll: 0,
-            visible_rows: 10,
-            visible_cols: 5,
-            autocomplete: AutocompleteState {
-                suggestions: Vec::new(),
-                selected: 0,
-                visible: false,
-            },
-            schema,
-            focus: Pane::Editor,
-            query_history: Vec::new(),
-            history_index: None,
-            history_draft: None,
-            history_path: unique_temp_path("history"),
-            table_picker: TablePickerState { visible: false, filter: String::new(), selected: 0 },
-        }
+    #[test]
+    fn statement_at_picks_the_statement_around_an_offset() {
+        let sql = "select 1; select 'a;b' ;\n";
+        assert_eq!(statement_at(sql, 0).as_deref(), Some("select 1"));
+        assert_eq!(statement_at(sql, 8).as_deref(), Some("select 1"));
+        assert_eq!(statement_at(sql, 12).as_deref(), Some("select 'a;b'"));
+        assert_eq!(statement_at(sql, sql.len()).as_deref(), Some("select 'a;b'"));
+        assert_eq!(statement_at("  ", 1), None);
     }
 
     #[test]
-    fn completion_kind_context_rules() {
-        assert_eq!(completion_kind("select "), CompletionKind::Column);
-        assert_eq!(completion_kind("select id from "), CompletionKind::Table);
-        assert_eq!(completion_kind("select * from users join "), CompletionKind::Table);
-        assert_eq!(completion_kind("select * from users on "), CompletionKind::Column);
-        assert_eq!(completion_kind("select * from users where "), CompletionKind::Keyword);
+    fn dry_run_shows_statements_without_running_them() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query(
+            "create table t(x);\nwith recursive n(x) as (select 1 union all select x + 1 from n) \
+             select x from n",
+        );
+        app.dry_run();
+        let popup = app.popup.as_ref().expect("dry run should open a popup");
+        assert_eq!(popup.lines[0], "-- statement 1 of 2");
+        assert_eq!(popup.lines[1], "create table t(x)");
+        let n = popup.lines.len();
+        assert!(popup.lines[n - 2].ends_with("select x from n"));
+        assert_eq!(popup.lines[n - 1], "LIMIT 1000");
+        assert!(app.tab().results.is_empty());
+        assert!(app.query_history.is_empty());
+        let tables: i64 = lock_connection(&app.conn)
+            .expect("connection should lock")
+            .query_row("select count(*) from sqlite_master", [], |row| row.get(0))
+            .expect("count should run");
+        assert_eq!(tables, 0);
+
+        app.set_query("select :name, ?, ?5 from t where x = :name or y = @other");
+        app.last_params = vec![
+            (":name".to_string(), rusqlite::types::Value::Text("o'k".to_string())),
+            ("?2".to_string(), rusqlite::types::Value::Integer(7)),
+        ];
+        app.dry_run();
+        let popup = app.popup.as_ref().expect("dry run should open a popup");
+        assert_eq!(popup.lines[0], "select 'o''k', 7, ?5 from t where x = 'o''k' or y = @other");
+        assert_eq!(
+            popup.lines.last().map(String::as_str),
+            Some("-- unbound: ?5, @other (`enter` asks for values)")
+        );
+        assert!(popup.lines.iter().any(|l| l.contains("for display only")));
+
+        app.bind_prompt = Some(BindPrompt {
+            statements: Vec::new(),
+            names: vec![":name".to_string()],
+            values: vec![(":name".to_string(), rusqlite::types::Value::Integer(1))],
+            input: String::new(),
+        });
+        app.dry_run();
+        let popup = app.popup.as_ref().expect("dry run should open a popup");
+        assert_eq!(popup.lines[0], "select 1, ?, ?5 from t where x = 1 or y = @other");
     }
 
     #[test]
-    fn truncate_helpers_are_unicode_safe() {
-        assert_eq!(truncate_left("abcdef", 4), "…def");
-        assert_eq!(truncate_right("abcdef", 4), "abc…");
-        assert_eq!(truncate_left("猫犬鳥", 2), "…鳥");
-        assert_eq!(truncate_right("猫犬鳥", 2), "猫…");
+    fn substitute_params_numbers_placeholders_like_sqlite() {
+        let sql = "select :a, ?, ?5, ?, @b, :a, '?' -- :c\n";
+        let conn = Connection::open_in_memory().expect("in-memory database should open");
+        // SQLite numbers the gap before `?5` too; those bind NULL
+        let keys = [":a", "?2", "?3", "?4", "?5", "?6", "@b"];
+        assert_eq!(parameter_names(&conn, &[sql.to_string()]), keys);
+        let params = vec![
+            (":a".to_string(), rusqlite::types::Value::Blob(vec![0xab, 1])),
+            ("?6".to_string(), rusqlite::types::Value::Null),
+        ];
+        let shown = substitute_params(sql, &params);
+        assert_eq!(shown.sql, "select X'AB01', ?, ?5, NULL, @b, X'AB01', '?' -- :c\n");
+        assert!(shown.substituted);
+        assert_eq!(shown.unbound, ["?2", "?5", "@b"]);
+        assert!(!substitute_params("select 1", &params).substituted);
     }
 
     #[test]
-    fn prefix_at_char_respects_char_boundaries() {
-        let s = "a猫b";
-        assert_eq!(prefix_at_char(s, 0), "");
-        assert_eq!(prefix_at_char(s, 1), "a");
-        assert_eq!(prefix_at_char(s, 2), "a猫");
-        assert_eq!(prefix_at_char(s, 3), "a猫b");
-        assert_eq!(prefix_at_char(s, 10), "a猫b");
+    fn transpose_pivots_and_restores_results() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        app.tab_mut().results =
+            vec![vec![CellValue::Integer(1), CellValue::Integer(2), CellValue::Null]];
+        assert!(transpose_is_useful(3, 1));
+        assert!(!transpose_is_useful(3, 2));
+
+        app.toggle_transpose();
+        assert_eq!(app.tab().headers, vec!["column".to_string(), "row 1".to_string()]);
+        assert_eq!(
+            app.tab().results,
+            vec![
+                vec![text("a"), CellValue::Integer(1)],
+                vec![text("b"), CellValue::Integer(2)],
+                vec![text("c"), CellValue::Null],
+            ]
+        );
+
+        app.toggle_transpose();
+        assert_eq!(app.tab().headers, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        assert!(app.tab().untransposed.is_none());
     }
 
     #[test]
@@ -1508,6 +6339,31 @@ mod tests {
         assert_ne!(p1, p2);
     }
 
+    #[test]
+    fn memory_and_uri_databases_are_passed_through() {
+        let memory = resolve_database_path(":memory:").expect(":memory: should resolve");
+        assert_eq!(memory, PathBuf::from(":memory:"));
+        assert_eq!(history_file_path_for_database(&memory).expect("no history"), None);
+
+        let path = unique_temp_path("uri-db");
+        Connection::open(&path)
+            .and_then(|conn| conn.execute_batch("create table t(x); insert into t values (1);"))
+            .expect("database should be created");
+        let uri = format!("file:{}?mode=ro", path.display());
+        let resolved = resolve_database_path(&uri).expect("URI should resolve");
+        assert_eq!(resolved, PathBuf::from(&uri));
+        let history = history_file_path_for_database(&resolved).expect("history path");
+        assert_eq!(history, history_file_path_for_database(&resolved).expect("history path"));
+        assert!(history.is_some());
+
+        let conn = open_database(&uri, 0, false, &[]).expect("URI should open");
+        let x: i64 = conn.query_row("select x from t", [], |row| row.get(0)).expect("row");
+        assert_eq!(x, 1);
+        assert!(conn.execute("insert into t values (2)", []).is_err(), "mode=ro is honoured");
+        assert!(open_database(":memory:", 0, false, &[]).is_ok());
+        fs::remove_file(&path).expect("database should be removed");
+    }
+
     #[test]
     fn history_roundtrip_preserves_queries() {
         let path = unique_temp_path("roundtrip");
@@ -1519,14 +6375,39 @@ mod tests {
         let _ = fs::remove_file(path);
     }
 
+    #[test]
+    fn history_roundtrip_preserves_whitespace_and_nul_bytes() {
+        let path = unique_temp_path("roundtrip-bytes");
+        let history = vec![
+            "select 1;\n".to_string(),
+            "select\r\n  2;\r\n".to_string(),
+            "select 'a\0b';".to_string(),
+            "select '猫';".to_string(),
+        ];
+        save_query_history(&path, &history).expect("history should save");
+        let loaded = load_query_history(&path).expect("history should load");
+        assert_eq!(loaded, history);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn legacy_nul_separated_history_still_loads() {
+        assert_eq!(
+            decode_query_history(b"select 1;\0select\n2;\0"),
+            vec!["select 1;".to_string(), "select\n2;".to_string()]
+        );
+    }
+
+    #[test]
+    fn truncated_history_keeps_complete_entries() {
+        let mut bytes = encode_query_history(&["select 1;".to_string()]);
+        bytes.extend_from_slice(b"40:select 2");
+        assert_eq!(decode_query_history(&bytes), vec!["select 1;".to_string()]);
+    }
+
     #[test]
     fn append_run_query_skips_consecutive_duplicates() {
-        let schema = Schema {
-            tables: vec![],
-            columns: vec![],
-            columns_by_table: std::collections::HashMap::new(),
-        };
-        let mut app = test_app_with_schema(schema);
+        let mut app = test_app_with_schema(empty_schema());
         app.append_run_query_to_history("select 1;");
         app.append_run_query_to_history("select 1;");
         app.append_run_query_to_history("select 2;");
@@ -1535,17 +6416,976 @@ mod tests {
     }
 
     #[test]
-    fn table_picker_applies_select_with_columns_in_order() {
+    fn results_position_formats_ranges_with_separators() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(3420), "3,420");
+        assert_eq!(format_count(1234567), "1,234,567");
+        assert_eq!(
+            results_position((50, 100), 3420, (0, 6), 14),
+            " rows 51–100 of 3,420 | cols 1–6 of 14 "
+        );
+        assert_eq!(results_position((0, 0), 0, (0, 2), 2), " 0 rows | cols 1–2 of 2 ");
+        assert_eq!(results_position((0, 0), 0, (0, 0), 0), "");
+    }
+
+    #[test]
+    fn column_widths_are_capped_and_cells_fit() {
+        let headers = vec!["id".to_string(), "x".repeat(100)];
+        let results = vec![vec![CellValue::Integer(1), text("short")]];
+        assert_eq!(column_widths(&headers, &results, &[], 200), vec![2, MAX_COLUMN_WIDTH as u16]);
+        assert_eq!(column_widths(&headers, &results, &[], 10), vec![2, 10]);
+        assert_eq!(fit_cell("a\tb\nc", 10), "a b c");
+        assert_eq!(fit_cell("abcdef", 4), "abc…");
+    }
+
+    #[tokio::test]
+    async fn long_quoted_column_name_is_truncated_in_header() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query(
+            "SELECT 1 AS \"a very long column name with spaces, commas and more text\", 2 AS b",
+        );
+        app.execute_query().await.expect("query should run");
+
+        let screen = render_screen(&mut app, 40, 20);
+        let header_row = screen
+            .lines()
+            .find(|line| line.contains("a very long") && line.contains('…'))
+            .expect("header row should be rendered");
+        assert!(header_row.contains("a very long column name with spaces…"), "{header_row}");
+        assert!(header_row.trim_end().ends_with('│'), "{header_row}");
+    }
+
+    #[tokio::test]
+    async fn attached_databases_are_qualified_and_survive_the_sandbox() {
+        let dir = unique_temp_path("attach");
+        fs::create_dir_all(&dir).expect("temp dir should be created");
+        let main_path = dir.join("main.db").to_string_lossy().to_string();
+        let aux_path = dir.join("aux1.db").to_string_lossy().to_string();
+        Connection::open(&aux_path)
+            .and_then(|conn| conn.execute_batch("create table orders(id, total);"))
+            .expect("fixture database should be created");
+        let attachments = attachments_for(&[aux_path.clone(), "x/2-aux1.sqlite".to_string()]);
+        assert_eq!(
+            attachments.iter().map(|a| a.alias.as_str()).collect::<Vec<_>>(),
+            ["aux1", "db2_aux1"]
+        );
+
+        let mut app = test_app_with_schema(empty_schema());
+        app.attachments = attachments_for(&[aux_path]);
+        app.conn = Arc::new(Mutex::new(
+            open_database(&main_path, 0, false, &app.attachments).expect("database should open"),
+        ));
+        app.schema = App::load_schema(&lock_connection(&app.conn).expect("connection should lock"))
+            .expect("schema should load");
+        assert_eq!(app.schema.tables, ["aux1.orders"]);
+        assert_eq!(column_names(&app.schema, "aux1.orders"), ["id", "total"]);
+        assert_eq!(column_names(&app.schema, "orders"), ["id", "total"]);
+        assert_eq!(app.quote_table("aux1.orders"), "\"aux1\".\"orders\"");
+
+        app.set_query("select * from aux1.or");
+        app.update_autocomplete();
+        assert_eq!(app.autocomplete.suggestions, ["orders"]);
+
+        app.run_command("sandbox").await.expect("sandbox should start");
+        app.set_query("insert into aux1.orders values (1, 9.5); select count(*) from orders;");
+        app.execute_query().await.expect("sandbox insert should run");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(1)]]);
+        app.run_command("discard").await.expect("sandbox should discard");
+        app.set_query("select count(*) from aux1.orders;");
+        app.execute_query().await.expect("count should run");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(0)]]);
+    }
+
+    #[tokio::test]
+    async fn sandbox_changes_are_discarded() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("create table t (id integer); insert into t values (1), (2);");
+        app.execute_query().await.expect("setup should run");
+
+        app.run_command("sandbox").await.expect("sandbox should start");
+        assert!(app.sandbox_origin.is_some());
+        app.set_query("delete from t; select count(*) from t;");
+        app.execute_query().await.expect("sandbox delete should run");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(0)]]);
+
+        app.run_command("discard").await.expect("sandbox should discard");
+        assert!(app.sandbox_origin.is_none());
+        app.set_query("select count(*) from t;");
+        app.execute_query().await.expect("count should run");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(2)]]);
+    }
+
+    #[test]
+    fn results_cursor_stays_in_bounds_and_scrolls() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = (0..8).map(|i| format!("c{}", i)).collect();
+        app.tab_mut().results = vec![vec![CellValue::Null; 8]; 4];
+        app.visible_rows = 2;
+        app.visible_cols = 3;
+
+        app.select_prev_row();
+        app.select_prev_col();
+        assert_eq!(app.tab().cursor(), (0, 0));
+
+        for _ in 0..20 {
+            app.select_next_row();
+            app.select_next_col();
+        }
+        assert_eq!((app.tab().current_row, app.tab().vertical_scroll), (3, 2));
+        assert_eq!((app.tab().current_col, app.tab().horizontal_scroll), (7, 5));
+
+        app.select_prev_row();
+        app.select_prev_row();
+        assert_eq!((app.tab().current_row, app.tab().vertical_scroll), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn clear_command_confirms_with_row_count() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("create table Items (id integer); insert into items values (1), (2), (3);");
+        app.execute_query().await.expect("setup should run");
+        let schema = App::load_schema(&lock_connection(&app.conn).expect("connection should lock"));
+        app.schema = schema.expect("schema should load");
+
+        app.run_command("clear nope").await.expect("command should run");
+        assert!(app.confirm.is_none());
+        assert_eq!(app.status, "No such table: nope");
+
+        app.run_command("clear items").await.expect("command should run");
+        let confirm = app.confirm.as_ref().expect("clear should ask for confirmation");
+        assert!(confirm.message.contains("all 3 rows from Items"), "{}", confirm.message);
+
+        app.handle_confirm_key(crossterm::event::KeyEvent::from(KeyCode::Char('x')))
+            .await
+            .expect("key handled");
+        assert!(app.confirm.is_some());
+        app.handle_confirm_key(crossterm::event::KeyEvent::from(KeyCode::Char('y')))
+            .await
+            .expect("clear runs");
+        assert!(app.confirm.is_none());
+        assert_eq!(app.status, "Deleted 3 rows from Items");
+
+        app.set_query("select count(*) from items;");
+        app.execute_query().await.expect("count should run");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(0)]]);
+    }
+
+    #[tokio::test]
+    async fn tail_refresh_marks_new_rows_and_skips_writes() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tail_interval = Some(Duration::from_secs(2));
+        lock_connection(&app.conn)
+            .expect("connection should lock")
+            .execute_batch("create table q (id integer); insert into q values (1), (2);")
+            .expect("setup should run");
+        app.set_query("select id from q order by id;");
+
+        app.refresh_tail().await.expect("first tick should run the editor query");
+        assert_eq!(app.tab().results.len(), 2);
+        assert!(app.tab().changed_rows.is_empty());
+        app.select_next_row();
+
+        lock_connection(&app.conn)
+            .expect("connection should lock")
+            .execute("insert into q values (3)", [])
+            .expect("insert should run");
+        app.refresh_tail().await.expect("refresh should run");
+        assert_eq!(app.tab().changed_rows, vec![false, false, true]);
+        assert_eq!(app.tab().current_row, 1);
+        assert_eq!(app.status, "3 rows, 1 changed (refreshing every 2s)");
+
+        app.last_statements = Some(vec!["insert into q values (4)".to_string()]);
+        app.refresh_tail().await.expect("write should be skipped");
+        assert_eq!(app.tab().results.len(), 3);
+        assert_eq!(app.status, "Tail paused: the last query modifies data");
+    }
+
+    #[tokio::test]
+    async fn auto_refresh_toggles_and_pauses_when_the_query_is_edited() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("select 1;");
+        app.execute_query().await.expect("query should run");
+        app.set_query("select 2 as n;");
+        app.toggle_auto_refresh();
+        assert_eq!(app.tail_interval, Some(Duration::from_secs(DEFAULT_REFRESH_SECS)));
+        assert_eq!(app.status, "Auto-refresh every 5s (R to stop)");
+
+        app.refresh_tail().await.expect("first tick should run the editor query");
+        assert_eq!(app.tab().headers, ["n"]);
+        assert_eq!(app.result_tabs.len(), 2);
+        app.refresh_tail().await.expect("refresh should run");
+        assert_eq!(app.result_tabs.len(), 2, "refreshes reuse the tab");
+
+        let screen = render_screen(&mut app, 60, 20);
+        assert!(screen.contains("⟳ 5s  /tmp/test.db"), "{screen}");
+
+        app.pause_refresh_if_edited();
+        assert!(app.tail_interval.is_some());
+        app.set_query("select 3 as n;");
+        app.pause_refresh_if_edited();
+        assert!(app.tail_interval.is_none());
+        assert_eq!(app.status, "Auto-refresh paused: query edited (R to resume)");
+
+        app.toggle_auto_refresh();
+        app.toggle_auto_refresh();
+        assert!(app.tail_interval.is_none());
+        assert_eq!(app.status, "Auto-refresh off");
+    }
+
+    #[tokio::test]
+    async fn tail_keeps_refreshing_after_the_query_is_edited() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tail_interval = Some(Duration::from_secs(2));
+        app.set_query("select 1 as n;");
+        app.refresh_tail().await.expect("first tick should run");
+        app.set_query("select 2 as n;");
+        app.pause_refresh_if_edited();
+        assert_eq!(app.tail_interval, Some(Duration::from_secs(2)));
+        assert!(app.status.ends_with("(refreshing every 2s)"), "{}", app.status);
+    }
+
+    #[test]
+    fn start_mode_honours_flag_and_config() {
+        let mut config = Config::default();
+        assert!(matches!(start_mode(false, &config), EditorMode::Insert));
+        assert!(matches!(start_mode(true, &config), EditorMode::Normal));
+        config.set("editor", "start_in_normal", Value::Boolean(true));
+        assert!(matches!(start_mode(false, &config), EditorMode::Normal));
+    }
+
+    #[tokio::test]
+    async fn normal_mode_start_runs_loaded_query_without_autocomplete() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.editor_state.mode = EditorMode::Normal;
+        app.set_query("select 1;");
+        app.update_autocomplete();
+        assert!(!app.autocomplete.visible);
+        app.execute_query().await.expect("query should run");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(1)]]);
+    }
+
+    #[tokio::test]
+    async fn col_command_completes_and_jumps_to_column() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = ["id", "created_at", "Created By", "updated_at", "total"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        app.tab_mut().results = vec![vec![CellValue::Null; 5]];
+        app.visible_cols = 2;
+
+        app.open_command_line_with("col cr");
+        app.complete_command_line();
+        assert_eq!(app.command_line.input, "col created");
+        app.command_line.input = "col to".to_string();
+        app.complete_command_line();
+        assert_eq!(app.command_line.input, "col total");
+
+        app.run_command("col created by").await.expect("command should run");
+        assert_eq!((app.tab().current_col, app.tab().horizontal_scroll), (2, 2));
+        app.run_command("col upd").await.expect("command should run");
+        assert_eq!((app.tab().current_col, app.tab().horizontal_scroll), (3, 2));
+        app.run_command("col ID").await.expect("command should run");
+        assert_eq!((app.tab().current_col, app.tab().horizontal_scroll), (0, 0));
+        app.run_command("col nope").await.expect("command should run");
+        assert_eq!(app.status, "No column matching nope");
+    }
+
+    #[test]
+    fn viewport_matches_rendered_size_before_first_draw() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = (0..20).map(|i| format!("column_{}", i)).collect();
+        app.tab_mut().results = vec![vec![text("value"); 20]; 100];
+        let area = Rect::new(0, 0, 80, 50);
+        app.fit_viewport(area);
+        let (rows, cols) = (app.visible_rows, app.visible_cols);
+        assert_ne!(rows, 10);
+
+        render_screen(&mut app, area.width, area.height);
+        assert_eq!((app.visible_rows, app.visible_cols), (rows, cols));
+    }
+
+    #[tokio::test]
+    async fn read_only_session_rejects_writes_with_clear_error() {
+        let path = unique_temp_path("read-only.db");
+        Connection::open(&path)
+            .and_then(|conn| conn.execute_batch("create table t(x); insert into t values (1);"))
+            .expect("fixture database should be created");
+        let mut app = test_app_with_schema(empty_schema());
+        app.read_only = true;
+        app.conn = Arc::new(Mutex::new(
+            open_database(&path.to_string_lossy(), 0, true, &[]).expect("database should open"),
+        ));
+
+        app.set_query("delete from t");
+        let err = app.execute_query().await.expect_err("write should be rejected");
+        assert!(
+            format_user_error(&err).starts_with("Database is read-only [SQLITE_READONLY]"),
+            "{err}"
+        );
+        app.run_command("clear t").await.expect("command should run");
+        assert_eq!(app.status, ":clear is disabled with --read-only (try :sandbox)");
+        assert!(app.confirm.is_none());
+
+        app.set_query("select x from t");
+        app.execute_query().await.expect("reads should still work");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(1)]]);
+
+        let screen = render_screen(&mut app, 60, 24);
+        assert!(screen.contains("Query [RO]"), "{screen}");
+        let _ = fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn export_writes_untransposed_results_to_given_path() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("select 1 as id, null as note union all select 2, 'NULL'");
+        app.execute_query().await.expect("query should run");
+        app.toggle_transpose();
+
+        let dir = unique_temp_path("export dir");
+        fs::create_dir_all(&dir).expect("temp dir should be created");
+        let path = dir.join("out file.json");
+        app.run_command(&format!("export json {}", path.display()))
+            .await
+            .expect("export should succeed");
+        assert_eq!(app.status, format!("Exported 2 rows to {}", path.display()));
+        assert_eq!(
+            fs::read_to_string(&path).expect("export should exist"),
+            "[\n  {\"id\": 1, \"note\": null},\n  {\"id\": 2, \"note\": \"NULL\"}\n]\n"
+        );
+
+        app.run_command("export xml").await.expect("command should run");
+        assert_eq!(app.status, "Unknown export format xml (csv or json)");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn default_export_path_sits_next_to_the_database() {
+        let path = default_export_path("/data/shop.db", ExportFormat::Csv).expect("path");
+        assert_eq!(path.parent(), Some(Path::new("/data")));
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        assert!(name.starts_with("shop-results-") && name.ends_with(".csv"), "{name}");
+
+        let cwd = env::current_dir().expect("cwd should be readable");
+        for uri in [MEMORY_DATABASE, "file:/data/shop.db?mode=ro"] {
+            let path = default_export_path(uri, ExportFormat::Json).expect("path");
+            assert_eq!(path.parent(), Some(cwd.as_path()), "{uri}");
+            let name =
+                path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            assert!(name.starts_with("squeal-results-") && name.ends_with(".json"), "{name}");
+        }
+    }
+
+    #[test]
+    fn expanded_row_lists_fields_vertically() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = (0..30).map(|i| format!("col_{}", i)).collect();
+        app.tab_mut().results =
+            (0..3).map(|r| (0..30).map(|c| text(&format!("r{}c{}", r, c))).collect()).collect();
+        app.tab_mut().results[1][1] = text(&"w".repeat(50));
+        app.toggle_expanded_row();
+        assert!(app.expanded_row);
+        app.select_next_row();
+
+        let screen = render_screen(&mut app, 40, 40);
+        let rows: Vec<&str> = screen.lines().collect();
+        assert!(screen.contains("Row 2 of 3"), "{screen}");
+        assert!(rows.iter().any(|r| r.contains("col_0   r1c0")), "{screen}");
+        // The long value wraps under its own column, leaving the label column blank
+        let long = rows.iter().position(|r| r.contains("col_1   www")).expect("long value shown");
+        assert!(rows[long + 1].contains("        www"), "{screen}");
+        assert!(!screen.contains("r0c0"), "{screen}");
+
+        app.toggle_expanded_row();
+        assert!(!app.expanded_row);
+    }
+
+    #[test]
+    fn cell_popup_shows_value_the_table_truncates() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = vec!["id".to_string(), "doc".to_string()];
+        let doc = format!("{{\"tags\": [{}]}}\nend", "\"x\", ".repeat(30));
+        app.tab_mut().results = vec![vec![CellValue::Integer(1), text(&doc)]];
+
+        let screen = render_screen(&mut app, 60, 20);
+        assert!(screen.contains("\"x\",…"), "{screen}");
+
+        app.select_next_col();
+        app.open_cell_value();
+        let popup = app.popup.as_ref().expect("cell popup should open");
+        assert_eq!(popup.title, " doc (row 1) ");
+        assert_eq!(popup.lines.join("\n"), doc);
+    }
+
+    #[test]
+    fn null_and_empty_text_render_differently_from_text() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        app.tab_mut().results = vec![
+            vec![CellValue::Integer(0), text("x"), text("y")],
+            vec![CellValue::Null, text("NULL"), text("")],
+        ];
+
+        let buffer = render_buffer(&mut app, 60, 20);
+        let find = |symbol: &str| {
+            let area = buffer.area;
+            (0..area.height)
+                .flat_map(|y| (0..area.width).map(move |x| (x, y)))
+                .filter(|&pos| buffer[pos].symbol() == symbol)
+                .collect::<Vec<(u16, u16)>>()
+        };
+        let empty = find(crate::value::EMPTY_TEXT_MARKER);
+        assert_eq!(empty.len(), 1);
+        assert_eq!(buffer[empty[0]].modifier, Modifier::DIM);
+        // The real NULL comes first on the row, then the text "NULL"
+        let nulls: Vec<Modifier> = find("N")
+            .into_iter()
+            .filter(|&(_, y)| y == empty[0].1)
+            .map(|pos| buffer[pos].modifier)
+            .collect();
+        assert_eq!(nulls, [Modifier::DIM | Modifier::ITALIC, Modifier::empty()]);
+    }
+
+    #[tokio::test]
+    async fn boolean_columns_show_true_and_false() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query(
+            "create table flags (id integer, active boolean); insert into flags values (0, 1), \
+             (1, 0), (2, 7); select id, active, active + 0 as n from flags;",
+        );
+        app.execute_query().await.expect("script should run");
+        assert_eq!(app.tab().boolean_columns, [false, true, false]);
+        let screen = render_screen(&mut app, 60, 20);
+        let row = |id: &str| {
+            let line = screen.lines().find(|l| l.contains(&format!("│{} ", id))).expect("row");
+            line.split_whitespace().filter(|w| *w != "│").collect::<Vec<_>>().join(" ")
+        };
+        assert!(row("0").contains("0 true 1"), "{}", screen);
+        assert!(row("1").contains("1 false 0"), "{}", screen);
+        assert!(row("2").contains("2 7 7"), "{}", screen);
+
+        app.toggle_transpose();
+        assert!(app.tab().untransposed.is_some());
+        assert!(!render_screen(&mut app, 60, 20).contains("true"));
+    }
+
+    #[test]
+    fn search_jumps_between_matching_cells() {
+        let key = |code| crossterm::event::KeyEvent::from(code);
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = (0..4).map(|i| format!("c{}", i)).collect();
+        app.tab_mut().results =
+            (0..50).map(|r| (0..4).map(|c| text(&format!("r{}c{}", r, c))).collect()).collect();
+        app.tab_mut().results[30][3] = text("Needle");
+        app.tab_mut().results[5][2] = text("a needle here");
+        app.tab_mut().results[40][0] = CellValue::Null;
+        app.visible_rows = 10;
+        app.visible_cols = 4;
+
+        app.open_search();
+        for ch in "NEED".chars() {
+            assert!(app.handle_command_line_key(key(KeyCode::Char(ch))).is_none());
+        }
+        assert_eq!(app.tab().cursor(), (5, 2));
+        assert_eq!(app.status, "Match 1 of 2 for need");
+        app.handle_command_line_key(key(KeyCode::Enter));
+        assert!(!app.command_line.visible);
+
+        app.jump_to_match(app.tab().cursor(), true, false);
+        assert_eq!(app.tab().cursor(), (30, 3));
+        assert_eq!(app.tab().vertical_scroll, 21);
+        app.jump_to_match(app.tab().cursor(), true, false);
+        assert_eq!(app.tab().cursor(), (5, 2));
+        app.jump_to_match(app.tab().cursor(), false, false);
+        assert_eq!(app.tab().cursor(), (30, 3));
+
+        app.tab_mut().current_row = 0;
+        app.tab_mut().vertical_scroll = 0;
+        // Match text and key hints take the theme's colours, e.g. for a light terminal
+        app.theme.highlight_fg = Color::Indexed(231);
+        app.theme.key = Color::Indexed(24);
+        let buffer = render_buffer(&mut app, 60, 24);
+        let cells =
+            || (0..buffer.area.height).flat_map(|y| (0..buffer.area.width).map(move |x| (x, y)));
+        let hit = cells()
+            .find(|&pos| buffer[pos].symbol() == "a" && buffer[(pos.0 + 2, pos.1)].symbol() == "n")
+            .expect("match should be on screen");
+        assert_eq!(buffer[hit].bg, Color::Rgb(229, 192, 123));
+        assert_eq!(buffer[hit].fg, Color::Indexed(231));
+        let hint = cells()
+            .find(|&pos| buffer[pos].symbol() == "e" && buffer[(pos.0 + 2, pos.1)].symbol() == "c")
+            .expect("esc hint should be on screen");
+        assert_eq!(buffer[hint].fg, Color::Indexed(24));
+
+        app.open_search();
+        app.handle_command_line_key(key(KeyCode::Char('z')));
+        assert_eq!(app.status, "No matches for z");
+        app.handle_command_line_key(key(KeyCode::Esc));
+        assert!(app.tab().search.is_none());
+    }
+
+    #[tokio::test]
+    async fn sort_toggles_direction_and_marks_header() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query(
+            "select 'b' as name, 10 as n union all select 'a', null union all select 'c', 9",
+        );
+        app.execute_query().await.expect("query should run");
+        app.tab_mut().current_col = 1;
+
+        app.sort_by_current_col();
+        let column = |app: &App| app.tab().results.iter().map(|r| r[1].clone()).collect::<Vec<_>>();
+        assert_eq!(
+            column(&app),
+            vec![CellValue::Integer(9), CellValue::Integer(10), CellValue::Null]
+        );
+        assert_eq!(app.status, "Sorted by n ascending");
+        app.sort_by_current_col();
+        assert_eq!(
+            column(&app),
+            vec![CellValue::Integer(10), CellValue::Integer(9), CellValue::Null]
+        );
+        assert_eq!(app.tab().sort, Some((1, true)));
+
+        let screen = render_screen(&mut app, 60, 24);
+        assert!(screen.contains("n ▼"), "{screen}");
+        assert_eq!(app.query_history.len(), 1, "sorting must not touch history");
+
+        app.execute_query().await.expect("query should run");
+        assert_eq!(app.tab().sort, None);
+    }
+
+    #[test]
+    fn history_picker_filters_and_loads_entries() {
+        let key = |code| crossterm::event::KeyEvent::from(code);
+        let mut app = test_app_with_schema(empty_schema());
+        app.query_history = vec![
+            "select * from orders;".to_string(),
+            "select id,\n  name\nfrom users;".to_string(),
+            "select count(*) from orders;".to_string(),
+        ];
+        app.set_query("draft");
+        app.editor_state.mode = EditorMode::Normal;
+        app.open_history_picker();
+        for ch in "ORD".chars() {
+            app.handle_history_picker_key(key(KeyCode::Char(ch)));
+        }
+        assert_eq!(app.filtered_history(), vec![2, 0]);
+        app.handle_history_picker_key(key(KeyCode::Down));
+        app.handle_history_picker_key(key(KeyCode::Down));
+        assert_eq!(app.history_picker.selected, 1);
+
+        app.handle_history_picker_key(key(KeyCode::Backspace));
+        app.handle_history_picker_key(key(KeyCode::Backspace));
+        app.handle_history_picker_key(key(KeyCode::Backspace));
+        let screen = render_screen(&mut app, 90, 30);
+        assert!(screen.contains("select id, name from users;"), "{screen}");
+
+        app.handle_history_picker_key(key(KeyCode::Down));
+        app.handle_history_picker_key(key(KeyCode::Enter));
+        assert!(!app.history_picker.visible);
+        assert_eq!(app.current_query(), "select id,\n  name\nfrom users;");
+        app.history_next();
+        app.history_next();
+        assert_eq!(app.current_query(), "draft");
+    }
+
+    #[test]
+    fn resize_keeps_cursor_in_view() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = (0..20).map(|i| format!("column_{}", i)).collect();
+        app.tab_mut().results = vec![vec![text("value"); 20]; 100];
+        app.fit_viewport(Rect::new(0, 0, 200, 80));
+        app.tab_mut().current_row = 60;
+        app.tab_mut().vertical_scroll = 40;
+        app.tab_mut().current_col = 15;
+        app.tab_mut().horizontal_scroll = 5;
+
+        app.fit_viewport(Rect::new(0, 0, 40, 24));
+        assert!(app.tab().vertical_scroll <= app.tab().current_row);
+        assert!(app.tab().current_row < app.tab().vertical_scroll + app.visible_rows);
+        assert!(app.tab().horizontal_scroll <= app.tab().current_col);
+        assert!(app.tab().current_col < app.tab().horizontal_scroll + app.visible_cols);
+
+        // Growing again pulls the view back so it isn't padded with blank rows
+        app.tab_mut().current_row = 99;
+        app.tab_mut().vertical_scroll = 99;
+        app.fit_viewport(Rect::new(0, 0, 200, 80));
+        assert_eq!(app.tab().vertical_scroll, 100 - app.visible_rows);
+    }
+
+    #[tokio::test]
+    async fn count_wraps_select_and_keeps_results() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("select 'kept';");
+        app.execute_query().await.expect("query should run");
+
+        app.set_query("select value from json_each('[1,2,3,4]') where value > 1");
+        app.count_current_query().await.expect("count should run");
+        assert_eq!(app.status, "Count: 3 rows");
+        assert_eq!(app.tab().results, vec![vec![text("kept")]]);
+
+        app.set_query("-- big ones\n/* only */ select 1 union all select 2 -- all of them");
+        app.count_current_query().await.expect("commented query should count");
+        assert_eq!(app.status, "Count: 2 rows");
+
+        app.set_query("delete from nowhere;");
+        app.count_current_query().await.expect("non-select should be refused");
+        assert_eq!(app.status, "Only SELECT queries can be counted");
+    }
+
+    #[tokio::test]
+    async fn user_version_can_be_read_set_and_shown() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.run_command("user_version").await.expect("command should run");
+        assert_eq!(app.status, "user_version = 0");
+        app.run_command("user_version 7").await.expect("command should run");
+        assert_eq!(app.status, "user_version set to 7 (was 0)");
+        app.run_command("user_version seven").await.expect("command should run");
+        assert!(app.status.starts_with("user_version must be"), "{}", app.status);
+
+        app.run_command("info").await.expect("command should run");
+        let popup = app.popup.as_ref().expect("info popup should open");
+        assert!(popup.lines.iter().any(|l| l == "user_version  7"), "{:?}", popup.lines);
+    }
+
+    #[tokio::test]
+    async fn scripts_log_each_statement_outcome() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query(
+            "create table s (id integer primary key); insert into s values (1), (2); select * \
+             from s",
+        );
+        app.execute_query().await.expect("script should run");
+        assert_eq!(
+            app.script_log,
+            vec![
+                "[1/3] CREATE TABLE ok".to_string(),
+                "[2/3] INSERT 2 rows".to_string(),
+                "[3/3] SELECT 2 rows".to_string(),
+            ]
+        );
+        assert!(app.status.ends_with("(3 statements, :log for details)"), "{}", app.status);
+
+        app.set_query("insert into s values (3); insert into s values (1); select 1;");
+        let err = app.execute_query().await.expect_err("duplicate key should fail");
+        assert!(err.to_string().contains("(1 of 3 statements ran, :log for details)"), "{err}");
+        assert_eq!(app.script_log.len(), 2);
+        assert!(app.script_log[1].starts_with("[2/3] INSERT ERROR: "), "{}", app.script_log[1]);
+
+        app.run_command("log").await.expect("command should run");
+        assert_eq!(app.popup.as_ref().map(|p| p.lines.len()), Some(2));
+        app.popup = None;
+
+        // Row-returning statements before the last are stepped, not just prepared
+        app.set_query("insert into s values (5), (6) returning id; select count(*) from s;");
+        app.execute_query().await.expect("script should run");
+        assert_eq!(app.script_log[0], "[1/2] INSERT 2 rows");
+        assert_eq!(app.tab().results, vec![vec![CellValue::Integer(5)]]);
+        app.set_query("select abs(-9223372036854775807 - 1); select 1;");
+        let err = app.execute_query().await.expect_err("overflow raised while stepping");
+        assert!(err.to_string().contains("(0 of 2 statements ran"), "{err}");
+
+        // In the app, finished statements show up while the rest of the script runs
+        app.queries_in_background = true;
+        app.set_query("select 1; select 2; select 3;");
+        app.execute_query().await.expect("script should start");
+        let QueryEvent::Progress(line) = query_event(&mut app.running).await else {
+            panic!("first statement should log before the script ends");
+        };
+        app.record_progress(line);
+        assert_eq!(app.status, "[1/3] SELECT 1 rows (esc to cancel)");
+        app.wait_for_query().await.expect("script should finish");
+        assert_eq!(app.script_log.len(), 3);
+        assert_eq!(app.script_log[2], "[3/3] SELECT 1 rows");
+    }
+
+    #[test]
+    fn trailing_semicolon_option_strips_loaded_queries() {
         let mut columns_by_table = std::collections::HashMap::new();
-        columns_by_table.insert(
-            "employees".to_string(),
-            vec!["id".to_string(), "first_name".to_string(), "last_name".to_string()],
+        columns_by_table.insert("t".to_string(), columns(&["id"]));
+        let mut app = test_app_with_schema(Schema::new(vec!["t".to_string()], columns_by_table));
+        app.trailing_semicolon = false;
+        app.query_history = vec!["select 1;\n".to_string()];
+
+        app.history_prev();
+        assert_eq!(app.current_query(), "select 1");
+        app.append_run_query_to_history("select 1");
+        assert_eq!(app.query_history.len(), 1);
+
+        app.open_table_picker();
+        assert!(app.table_picker_apply_selection());
+        assert_eq!(app.current_query(), "select id from t limit 100");
+
+        app.trailing_semicolon = true;
+        app.history_prev();
+        assert_eq!(app.current_query(), "select 1;\n");
+    }
+
+    #[test]
+    fn visual_selection_is_highlighted() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.set_query("select 1");
+        app.editor_state.mode = EditorMode::Normal;
+        app.editor_state.cursor.col = 0;
+        for ch in ['v', 'e'] {
+            app.event_handler.on_key_event(
+                crossterm::event::KeyEvent::from(KeyCode::Char(ch)),
+                &mut app.editor_state,
+            );
+        }
+        assert!(matches!(app.editor_state.mode, EditorMode::Visual));
+
+        let buffer = render_buffer(&mut app, 40, 20);
+        let highlighted: String = (0..buffer.area.height)
+            .flat_map(|y| (0..buffer.area.width).map(move |x| (x, y)))
+            .filter(|&pos| buffer[pos].bg == Color::Rgb(229, 192, 123))
+            .map(|pos| buffer[pos].symbol().to_string())
+            .collect();
+        // The last selected character sits under the cursor and takes the cursor style
+        assert_eq!(highlighted, "selec");
+    }
+
+    #[tokio::test]
+    async fn paths_command_lists_database_history_and_config() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.run_command("paths").await.expect("command should run");
+        let popup = app.popup.as_ref().expect("paths popup should open");
+        assert_eq!(popup.lines[0], "Database  /tmp/test.db");
+        let history = app.history_path.as_ref().expect("file databases keep history");
+        assert!(popup.lines[1].ends_with(&history.display().to_string()));
+
+        app.run_command("reveal nowhere").await.expect("command should run");
+        assert_eq!(app.status, "Unknown path nowhere (db, history, or config)");
+    }
+
+    #[tokio::test]
+    async fn table_style_toggles_render_and_persist() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.tab_mut().headers = vec!["n".to_string()];
+        app.tab_mut().results = (0..10).map(|i| vec![CellValue::Integer(i)]).collect();
+
+        app.run_command("separators").await.expect("command should run");
+        app.run_command("zebra").await.expect("command should run");
+        assert_eq!(app.status, "Zebra striping off");
+        let saved = Config::load(&app.config_path).expect("config should load");
+        assert_eq!(TableStyle::from_config(&saved), app.table_style);
+
+        fs::write(&app.config_path, "not toml\n").expect("config should be written");
+        app.config_load_failed = true;
+        let err = app.run_command("zebra").await.expect_err("save should be refused");
+        assert!(err.to_string().starts_with("Not saving:"));
+        assert_eq!(fs::read_to_string(&app.config_path).ok().as_deref(), Some("not toml\n"));
+        app.run_command("zebra").await.expect_err("save should be refused");
+        let _ = fs::remove_file(&app.config_path);
+
+        app.editor_collapsed = true;
+        let screen = render_screen(&mut app, 20, 14);
+        let lines: Vec<&str> = screen.lines().collect();
+        let first = lines.iter().position(|l| l.starts_with(" │0")).expect("row 0 rendered");
+        assert!(lines[first + 1].starts_with(" │──"), "{lines:#?}");
+        assert!(lines[first + 2].starts_with(" │1"), "{lines:#?}");
+        assert_eq!(app.visible_rows, 2);
+        let buffer = render_buffer(&mut app, 20, 14);
+        assert_eq!(buffer[(2, first as u16 + 2)].fg, Color::Rgb(212, 220, 232));
+    }
+
+    #[tokio::test]
+    async fn diff_history_compares_editor_with_previous_query() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.query_history = vec!["select a\nfrom t".to_string(), "select b\nfrom t".to_string()];
+        app.set_query("select b\nfrom t\nlimit 5");
+
+        app.run_command("diff-history").await.expect("command should run");
+        let popup = app.popup.take().expect("diff popup should open");
+        assert!(popup.diff);
+        assert_eq!(popup.lines, vec!["  select b", "  from t", "+ limit 5"]);
+
+        app.run_command("diff-history 2 1").await.expect("command should run");
+        let popup = app.popup.take().expect("diff popup should open");
+        assert_eq!(popup.lines, vec!["- select a", "+ select b", "  from t"]);
+
+        app.run_command("diff-history 1 9").await.expect("command should run");
+        assert!(app.popup.is_none());
+        assert_eq!(app.status, "History has 2 entries");
+    }
+
+    #[test]
+    fn autocomplete_popup_fits_suggestions_within_limits() {
+        let mut config = Config::default();
+        config.set("autocomplete", "max_height", Value::Integer(3));
+        config.set("autocomplete", "max_width", Value::Integer(30));
+        let limits = AutocompleteLimits::from_config(&config);
+        let names = |n: usize, len: usize| -> Vec<String> { vec!["x".repeat(len); n] };
+
+        assert_eq!(limits.popup_size(&names(2, 5)), (20, 2));
+        assert_eq!(limits.popup_size(&names(2, 25)), (25, 2));
+        assert_eq!(limits.popup_size(&names(10, 50)), (30, 3));
+        assert_eq!(AutocompleteLimits::default().popup_size(&names(20, 5)), (20, 8));
+        assert_eq!(AutocompleteLimits::default().popup_size(&names(1, 60)), (60, 1));
+    }
+
+    #[test]
+    fn unmatched_rows_pairs_duplicates() {
+        let row = |v: i64| vec![CellValue::Integer(v)];
+        let a = vec![row(1), row(1), row(2)];
+        let b = vec![row(1), row(3)];
+        assert_eq!(unmatched_rows(&a, &b), vec![false, true, true]);
+        assert_eq!(unmatched_rows(&b, &a), vec![false, true]);
+    }
+
+    #[tokio::test]
+    async fn compare_runs_queries_against_both_databases() {
+        let other = Connection::open_in_memory().expect("in-memory database should open");
+        other
+            .execute_batch("create table t (id integer); insert into t values (1), (3);")
+            .expect("other database setup should run");
+        let mut app = test_app_with_schema(empty_schema());
+        app.compare =
+            Some(ComparePane { name: "other.db".to_string(), conn: Arc::new(Mutex::new(other)) });
+        lock_connection(&app.conn)
+            .expect("connection should lock")
+            .execute_batch("create table t (id integer); insert into t values (1), (2), (2);")
+            .expect("main database setup should run");
+
+        app.set_query("select id from t order by id;");
+        app.execute_query().await.expect("query should run");
+        let compare = app.tab().compare.as_ref().expect("compare results should be kept");
+        assert_eq!(compare.results, vec![vec![CellValue::Integer(1)], vec![CellValue::Integer(3)]]);
+        assert_eq!(compare.unmatched, vec![false, true]);
+        assert!(app.status.starts_with("3 rows returned in "), "{}", app.status);
+        assert!(app.status.ends_with(" | other.db: 2 rows, 2 only in main, 1 only in other.db"));
+
+        app.set_query("select 1;");
+        app.execute_query().await.expect("query should run");
+        assert!(app.status.ends_with("other.db: identical"), "{}", app.status);
+
+        // Writes only reach the main database, even from a sandbox
+        app.run_command("sandbox").await.expect("sandbox should start");
+        app.set_query("delete from t;");
+        app.execute_query().await.expect("delete should run");
+        assert!(app.status.ends_with("other.db: not run (query writes data)"), "{}", app.status);
+        let compare = app.compare.as_ref().expect("compare pane should exist");
+        let kept: i64 = lock_connection(&compare.conn)
+            .expect("connection should lock")
+            .query_row("select count(*) from t", [], |row| row.get(0))
+            .expect("count should run");
+        assert_eq!(kept, 2);
+    }
+
+    #[test]
+    fn compare_database_must_exist() {
+        let path = unique_temp_path("missing-compare");
+        assert!(open_database(&path.to_string_lossy(), 0, true, &[]).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn sql_errors_carry_extended_code_hints() {
+        let conn = Connection::open_in_memory().expect("in-memory database should open");
+        conn.execute_batch(
+            "pragma foreign_keys = on;
+             create table parent (id integer primary key);
+             create table child (parent_id integer references parent(id));",
+        )
+        .expect("schema should be created");
+
+        let sql = "insert into child values (42)";
+        let err = conn.execute(sql, []).expect_err("insert should violate the foreign key");
+        let msg = format_sql_error(&err, sql);
+        assert!(msg.starts_with("Constraint violation [SQLITE_CONSTRAINT_FOREIGNKEY]"), "{msg}");
+        assert!(msg.contains("foreign_key_list"), "{msg}");
+        assert_eq!(format_user_error(&anyhow::anyhow!(msg.clone())), msg);
+
+        conn.execute("insert into parent values (1)", []).expect("parent insert should succeed");
+        let sql = "insert into parent values (1)";
+        let err = conn.execute(sql, []).expect_err("duplicate key should fail");
+        assert!(format_sql_error(&err, sql).contains("SQLITE_CONSTRAINT_PRIMARYKEY"));
+    }
+
+    #[test]
+    fn collapsing_editor_gives_results_the_screen() {
+        let mut app = test_app_with_schema(empty_schema());
+        render_screen(&mut app, 40, 30);
+        let expanded_rows = app.visible_rows;
+
+        app.toggle_editor_collapsed();
+        assert_eq!(app.focus, Pane::Results);
+        render_screen(&mut app, 40, 30);
+        assert_eq!(app.visible_rows, expanded_rows + 7);
+
+        app.toggle_editor_collapsed();
+        assert_eq!(app.focus, Pane::Editor);
+    }
+
+    #[test]
+    fn layout_roundtrips_through_config_and_clamps() {
+        let mut layout = LayoutSettings {
+            orientation: SplitOrientation::Horizontal,
+            ..LayoutSettings::default()
+        };
+        layout.resize_editor(3);
+        assert_eq!(layout.editor_width_percent, 55);
+        layout.resize_editor(100);
+        assert_eq!(layout.editor_width_percent, LayoutSettings::WIDTH_PERCENT_RANGE.1);
+
+        let mut config = Config::default();
+        layout.write_to(&mut config);
+        let reparsed = Config::parse(&config.to_string()).expect("config should parse");
+        assert_eq!(LayoutSettings::from_config(&reparsed), layout);
+
+        config.set("layout", "editor_height", Value::Integer(1000));
+        assert_eq!(
+            LayoutSettings::from_config(&config).editor_height,
+            LayoutSettings::HEIGHT_RANGE.1
         );
-        let schema = Schema {
-            tables: vec!["employees".to_string()],
-            columns: vec!["id".to_string(), "first_name".to_string(), "last_name".to_string()],
-            columns_by_table,
+    }
+
+    #[test]
+    fn layout_is_saved_on_exit_only_when_changed() {
+        let mut app = test_app_with_schema(empty_schema());
+        app.save_layout_on_exit();
+        assert!(!app.config_path.exists());
+
+        app.toggle_split_orientation();
+        app.layout.resize_editor(-2);
+        app.save_layout_on_exit();
+        let saved = Config::load(&app.config_path).expect("config should load");
+        assert_eq!(LayoutSettings::from_config(&saved), app.layout);
+
+        fs::write(&app.config_path, "not toml\n").expect("config should be written");
+        app.config_load_failed = true;
+        app.layout.resize_editor(2);
+        app.save_layout_on_exit();
+        assert_eq!(fs::read_to_string(&app.config_path).ok().as_deref(), Some("not toml\n"));
+        let _ = fs::remove_file(&app.config_path);
+    }
+
+    #[test]
+    fn horizontal_split_puts_editor_beside_results() {
+        let layout = LayoutSettings {
+            orientation: SplitOrientation::Horizontal,
+            editor_width_percent: 50,
+            ..LayoutSettings::default()
         };
+        let [editor, results, _, _] = main_layout(Rect::new(0, 0, 102, 40), &layout, false);
+        assert_eq!(editor.y, results.y);
+        assert_eq!(editor.width, 50);
+        assert_eq!(results.x, editor.x + editor.width);
+
+        let [editor, results, _, _] = main_layout(Rect::new(0, 0, 102, 40), &layout, true);
+        assert_eq!(editor.height, 3);
+        assert_eq!(results.y, editor.y + 3);
+    }
+
+    #[test]
+    fn table_picker_applies_select_with_columns_in_order() {
+        let mut columns_by_table = std::collections::HashMap::new();
+        columns_by_table
+            .insert("employees".to_string(), columns(&["id", "first_name", "last_name"]));
+        let schema = Schema::new(vec!["employees".to_string()], columns_by_table);
         let mut app = test_app_with_schema(schema);
         app.open_table_picker();
         let selected = app.table_picker_apply_selection();
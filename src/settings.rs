@@ -0,0 +1,137 @@
+//! Session PRAGMAs exposed in the `:settings` modal.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+
+use crate::config::{Config, Value};
+
+/// Config section holding PRAGMAs reapplied to every new session.
+pub const PRAGMA_SECTION: &str = "pragmas";
+
+pub struct PragmaSetting {
+    pub name: &'static str,
+    /// Values cycled through by the settings modal, in display form.
+    pub options: &'static [&'static str],
+}
+
+pub const PRAGMA_SETTINGS: &[PragmaSetting] = &[
+    PragmaSetting { name: "foreign_keys", options: &["OFF", "ON"] },
+    PragmaSetting {
+        name: "journal_mode",
+        options: &["DELETE", "TRUNCATE", "PERSIST", "MEMORY", "WAL", "OFF"],
+    },
+    PragmaSetting { name: "synchronous", options: &["OFF", "NORMAL", "FULL", "EXTRA"] },
+    PragmaSetting { name: "cache_size", options: &["-2000", "-8000", "-32000", "-131072"] },
+];
+
+pub struct SettingsState {
+    pub selected: usize,
+    /// Live value of each entry in `PRAGMA_SETTINGS`, in display form.
+    pub values: Vec<String>,
+}
+
+pub fn find_setting(name: &str) -> Option<&'static PragmaSetting> {
+    PRAGMA_SETTINGS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Current value of a known PRAGMA, with enum-like integers mapped to their names.
+pub fn read_pragma(conn: &Connection, setting: &PragmaSetting) -> Result<String> {
+    let raw: rusqlite::types::Value = conn
+        .query_row(&format!("PRAGMA {}", setting.name), [], |row| row.get(0))
+        .with_context(|| format!("Failed to read PRAGMA {}", setting.name))?;
+    Ok(match raw {
+        rusqlite::types::Value::Integer(i) if setting.name == "cache_size" => i.to_string(),
+        rusqlite::types::Value::Integer(i) => usize::try_from(i)
+            .ok()
+            .and_then(|idx| setting.options.get(idx))
+            .map_or_else(|| i.to_string(), ToString::to_string),
+        rusqlite::types::Value::Text(s) => s.to_uppercase(),
+        other => format!("{:?}", other),
+    })
+}
+
+pub fn read_all(conn: &Connection) -> Result<Vec<String>> {
+    PRAGMA_SETTINGS.iter().map(|setting| read_pragma(conn, setting)).collect()
+}
+
+/// Sets a known PRAGMA. Values are validated so config contents can't inject SQL.
+pub fn apply_pragma(conn: &Connection, setting: &PragmaSetting, value: &str) -> Result<()> {
+    let valid = setting.options.iter().any(|o| o.eq_ignore_ascii_case(value))
+        || (setting.name == "cache_size" && value.parse::<i64>().is_ok());
+    if !valid {
+        anyhow::bail!("Invalid value {} for PRAGMA {}", value, setting.name);
+    }
+    // Some PRAGMAs (journal_mode) answer with a row, so read it rather than `execute`
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA {} = {}", setting.name, value))
+        .with_context(|| format!("Failed to set PRAGMA {}", setting.name))?;
+    let mut rows =
+        stmt.query([]).with_context(|| format!("Failed to set PRAGMA {}", setting.name))?;
+    while rows.next()?.is_some() {}
+    Ok(())
+}
+
+/// Option following `current`, wrapping around; unknown values restart at the first option.
+pub fn next_option(setting: &PragmaSetting, current: &str) -> &'static str {
+    let idx = setting.options.iter().position(|o| o.eq_ignore_ascii_case(current));
+    let next = idx.map_or(0, |i| (i + 1) % setting.options.len());
+    setting.options[next]
+}
+
+/// Applies every PRAGMA saved in `config`, returning a message for each that failed.
+pub fn apply_saved(conn: &Connection, config: &Config) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (name, value) in config.entries(PRAGMA_SECTION) {
+        let Some(setting) = find_setting(name) else {
+            errors.push(format!("Unknown PRAGMA {} in config", name));
+            continue;
+        };
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Integer(i) => i.to_string(),
+            other => other.to_string(),
+        };
+        if let Err(e) = apply_pragma(conn, setting, &value) {
+            errors.push(e.to_string());
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_and_cycles_pragmas() {
+        let conn = Connection::open_in_memory().expect("in-memory database should open");
+        let foreign_keys = find_setting("foreign_keys").expect("setting should exist");
+        let initial = read_pragma(&conn, foreign_keys).expect("pragma should read");
+        assert!(foreign_keys.options.contains(&initial.as_str()), "{initial}");
+
+        let next = next_option(foreign_keys, &initial);
+        assert_ne!(next, initial);
+        apply_pragma(&conn, foreign_keys, next).expect("pragma should apply");
+        assert_eq!(read_pragma(&conn, foreign_keys).expect("pragma should read"), next);
+        assert_eq!(next_option(foreign_keys, next), initial);
+    }
+
+    #[test]
+    fn rejects_values_outside_known_options() {
+        let conn = Connection::open_in_memory().expect("in-memory database should open");
+        let synchronous = find_setting("synchronous").expect("setting should exist");
+        assert!(apply_pragma(&conn, synchronous, "FULL; DROP TABLE x").is_err());
+    }
+
+    #[test]
+    fn applies_saved_config_and_reports_bad_entries() {
+        let conn = Connection::open_in_memory().expect("in-memory database should open");
+        let mut config = Config::default();
+        config.set(PRAGMA_SECTION, "cache_size", Value::Integer(-8000));
+        config.set(PRAGMA_SECTION, "bogus", Value::Boolean(true));
+        let errors = apply_saved(&conn, &config);
+        assert_eq!(errors, vec!["Unknown PRAGMA bogus in config".to_string()]);
+        let cache_size = find_setting("cache_size").expect("setting should exist");
+        assert_eq!(read_pragma(&conn, cache_size).expect("pragma should read"), "-8000");
+    }
+}
@@ -0,0 +1,165 @@
+//! Client-side sorting of fetched results by one column.
+//!
+//! Columns whose non-NULL values are all numbers (or text that parses as one) sort numerically;
+//! anything else sorts by display text. NULL placement doesn't flip with the direction.
+
+use std::cmp::Ordering;
+
+use crate::{
+    config::{Config, Value},
+    value::CellValue,
+};
+
+/// Config section holding sort preferences.
+pub const SORT_SECTION: &str = "sort";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NullsOrder {
+    First,
+    #[default]
+    Last,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SortSettings {
+    pub nulls: NullsOrder,
+    /// Keep the fetched order of rows with equal keys.
+    pub stable: bool,
+}
+
+impl Default for SortSettings {
+    fn default() -> Self {
+        Self { nulls: NullsOrder::default(), stable: true }
+    }
+}
+
+impl SortSettings {
+    /// Reads `nulls = "first"|"last"` and `stable`, returning a warning for each invalid value.
+    pub fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut warnings = Vec::new();
+        match config.get(SORT_SECTION, "nulls") {
+            None => {},
+            Some(value) => match value.as_str().map(str::to_ascii_lowercase).as_deref() {
+                Some("first") => settings.nulls = NullsOrder::First,
+                Some("last") => settings.nulls = NullsOrder::Last,
+                _ => warnings
+                    .push(format!("[sort] nulls must be \"first\" or \"last\", got {}", value)),
+            },
+        }
+        match config.get(SORT_SECTION, "stable") {
+            None => {},
+            Some(Value::Boolean(stable)) => settings.stable = *stable,
+            Some(value) => {
+                warnings.push(format!("[sort] stable must be true or false, got {}", value))
+            },
+        }
+        (settings, warnings)
+    }
+}
+
+/// Row indices of `rows` ordered by column `col`.
+pub fn sorted_indices(
+    rows: &[Vec<CellValue>],
+    col: usize,
+    descending: bool,
+    settings: SortSettings,
+) -> Vec<usize> {
+    let cell = |i: usize| rows[i].get(col).unwrap_or(&CellValue::Null);
+    let numeric = rows.iter().all(|row| {
+        matches!(row.get(col), None | Some(CellValue::Null)) || numeric_value(&row[col]).is_some()
+    });
+    let compare = |&a: &usize, &b: &usize| match (cell(a), cell(b)) {
+        (CellValue::Null, CellValue::Null) => Ordering::Equal,
+        (CellValue::Null, _) => nulls_ordering(settings.nulls),
+        (_, CellValue::Null) => nulls_ordering(settings.nulls).reverse(),
+        (x, y) => {
+            let ordering =
+                if numeric { compare_numeric(x, y) } else { x.display().cmp(&y.display()) };
+            if descending { ordering.reverse() } else { ordering }
+        },
+    };
+    let mut indices: Vec<usize> = (0..rows.len()).collect();
+    if settings.stable {
+        indices.sort_by(compare);
+    } else {
+        indices.sort_unstable_by(compare);
+    }
+    indices
+}
+
+/// How a NULL compares to a non-NULL value.
+fn nulls_ordering(nulls: NullsOrder) -> Ordering {
+    match nulls {
+        NullsOrder::First => Ordering::Less,
+        NullsOrder::Last => Ordering::Greater,
+    }
+}
+
+fn numeric_value(cell: &CellValue) -> Option<f64> {
+    match cell {
+        CellValue::Integer(i) => Some(*i as f64),
+        CellValue::Real(f) => Some(*f),
+        CellValue::Text(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn compare_numeric(a: &CellValue, b: &CellValue) -> Ordering {
+    match (a, b) {
+        // Exact for integers too large to round-trip through f64
+        (CellValue::Integer(x), CellValue::Integer(y)) => x.cmp(y),
+        _ => {
+            let (x, y) =
+                (numeric_value(a).unwrap_or(f64::NAN), numeric_value(b).unwrap_or(f64::NAN));
+            x.total_cmp(&y)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(values: Vec<CellValue>) -> Vec<Vec<CellValue>> {
+        values.into_iter().map(|v| vec![v]).collect()
+    }
+
+    #[test]
+    fn sorts_numbers_numerically_with_nulls_last() {
+        let rows = column(vec![
+            CellValue::Text("10".to_string()),
+            CellValue::Null,
+            CellValue::Integer(9),
+            CellValue::Real(9.5),
+        ]);
+        let settings = SortSettings::default();
+        assert_eq!(sorted_indices(&rows, 0, false, settings), vec![2, 3, 0, 1]);
+        assert_eq!(sorted_indices(&rows, 0, true, settings), vec![0, 3, 2, 1]);
+
+        let nulls_first = SortSettings { nulls: NullsOrder::First, ..settings };
+        assert_eq!(sorted_indices(&rows, 0, true, nulls_first), vec![1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn mixed_text_sorts_lexicographically_and_stably() {
+        let rows = column(vec![
+            CellValue::Text("b".to_string()),
+            CellValue::Integer(10),
+            CellValue::Text("a".to_string()),
+            CellValue::Integer(9),
+            CellValue::Text("a".to_string()),
+        ]);
+        assert_eq!(sorted_indices(&rows, 0, false, SortSettings::default()), vec![1, 3, 2, 4, 0]);
+    }
+
+    #[test]
+    fn reads_settings_and_warns_on_bad_values() {
+        let mut config = Config::default();
+        config.set(SORT_SECTION, "nulls", Value::String("First".to_string()));
+        config.set(SORT_SECTION, "stable", Value::String("yes".to_string()));
+        let (settings, warnings) = SortSettings::from_config(&config);
+        assert_eq!(settings, SortSettings { nulls: NullsOrder::First, stable: true });
+        assert_eq!(warnings.len(), 1);
+    }
+}
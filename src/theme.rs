@@ -0,0 +1,150 @@
+//! Colours used by `ui`, overridable in the `[theme]` config section.
+//!
+//! Colours are ratatui colour names (`lightblue`), 256-colour indices (`245`, bare or quoted), or
+//! `#rrggbb`.
+
+use edtui::THEME_SET;
+use ratatui::style::Color;
+
+use crate::config::{Config, Value};
+
+/// Config section holding theme overrides.
+pub const THEME_SECTION: &str = "theme";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// syntect theme used to highlight the editor.
+    pub syntax: String,
+    pub text: Color,
+    pub muted: Color,
+    /// Focused border in Normal mode and pane titles.
+    pub accent: Color,
+    /// Unfocused border.
+    pub border: Color,
+    /// Focused border and title in Insert mode.
+    pub insert: Color,
+    /// Results border while the editor is in Insert mode.
+    pub insert_dim: Color,
+    /// Changed rows, search matches, and the Visual selection.
+    pub warn: Color,
+    /// Text drawn on `warn`: search matches and the Visual selection.
+    pub highlight_fg: Color,
+    pub error: Color,
+    pub selection: Color,
+    /// Background of the autocomplete popup.
+    pub panel: Color,
+    pub header: Color,
+    /// Even result rows; odd ones use `row_alt` when zebra striping is on.
+    pub row: Color,
+    pub row_alt: Color,
+    /// Key names in the footer hints.
+    pub key: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            syntax: String::from("charcoal"),
+            text: Color::Rgb(212, 220, 232),
+            muted: Color::Rgb(138, 152, 171),
+            accent: Color::White,
+            border: Color::Rgb(130, 130, 130),
+            insert: Color::Rgb(152, 195, 121),
+            insert_dim: Color::Rgb(98, 122, 84),
+            warn: Color::Rgb(229, 192, 123),
+            highlight_fg: Color::Black,
+            error: Color::Rgb(224, 108, 117),
+            selection: Color::Rgb(56, 63, 79),
+            panel: Color::Rgb(28, 32, 40),
+            header: Color::White,
+            row: Color::Rgb(212, 220, 232),
+            row_alt: Color::Rgb(138, 152, 171),
+            key: Color::White,
+        }
+    }
+}
+
+impl Theme {
+    /// Reads `[theme]` overrides, keeping the default and returning a warning for each invalid
+    /// value.
+    pub fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let mut theme = Self::default();
+        let mut warnings = Vec::new();
+        if let Some(value) = config.get(THEME_SECTION, "syntax") {
+            match value.as_str() {
+                Some(name) if THEME_SET.themes.contains_key(name) => {
+                    theme.syntax = name.to_string()
+                },
+                _ => {
+                    let known: Vec<&str> = THEME_SET.themes.keys().map(String::as_str).collect();
+                    warnings.push(format!(
+                        "[theme] unknown syntax theme {} (try {})",
+                        value,
+                        known.join(", ")
+                    ));
+                },
+            }
+        }
+        for (key, target) in [
+            ("text", &mut theme.text),
+            ("muted", &mut theme.muted),
+            ("accent", &mut theme.accent),
+            ("border", &mut theme.border),
+            ("insert", &mut theme.insert),
+            ("insert_dim", &mut theme.insert_dim),
+            ("warn", &mut theme.warn),
+            ("highlight_fg", &mut theme.highlight_fg),
+            ("error", &mut theme.error),
+            ("selection", &mut theme.selection),
+            ("panel", &mut theme.panel),
+            ("header", &mut theme.header),
+            ("row", &mut theme.row),
+            ("row_alt", &mut theme.row_alt),
+            ("key", &mut theme.key),
+        ] {
+            let Some(value) = config.get(THEME_SECTION, key) else {
+                continue;
+            };
+            match parse_color(value) {
+                Some(color) => *target = color,
+                None => warnings.push(format!("[theme] {} is not a colour: {}", key, value)),
+            }
+        }
+        (theme, warnings)
+    }
+}
+
+/// A colour name or `#rrggbb` string, or a 256-colour index given as a number or a string.
+fn parse_color(value: &Value) -> Option<Color> {
+    match value {
+        Value::Integer(n) => u8::try_from(*n).ok().map(Color::Indexed),
+        other => other.as_str()?.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Value;
+
+    #[test]
+    fn reads_overrides_and_warns_on_bad_values() {
+        let mut config = Config::default();
+        config.set(THEME_SECTION, "syntax", Value::String("no-such-theme".to_string()));
+        config.set(THEME_SECTION, "header", Value::String("#1e66f5".to_string()));
+        config.set(THEME_SECTION, "row_alt", Value::String("darkgray".to_string()));
+        config.set(THEME_SECTION, "warn", Value::String("orange-ish".to_string()));
+        config.set(THEME_SECTION, "row", Value::Integer(245));
+        config.set(THEME_SECTION, "key", Value::String("245".to_string()));
+        config.set(THEME_SECTION, "muted", Value::Integer(256));
+        let (theme, warnings) = Theme::from_config(&config);
+        assert_eq!(theme.syntax, Theme::default().syntax);
+        assert_eq!(theme.header, Color::Rgb(0x1e, 0x66, 0xf5));
+        assert_eq!(theme.row_alt, Color::DarkGray);
+        assert_eq!(theme.warn, Theme::default().warn);
+        assert_eq!((theme.row, theme.key), (Color::Indexed(245), Color::Indexed(245)));
+        assert_eq!(theme.muted, Theme::default().muted);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("charcoal"), "{}", warnings[0]);
+    }
+}
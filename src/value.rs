@@ -0,0 +1,182 @@
+//! Result cells kept with the type SQLite returned, so display, sorting, and export can each
+//! decide how to treat them instead of re-parsing display strings.
+
+use std::{
+    borrow::Cow,
+    hash::{Hash, Hasher},
+};
+
+use rusqlite::types::ValueRef;
+
+/// Shown in the results table for a zero-length text value.
+pub const EMPTY_TEXT_MARKER: &str = "∅";
+
+#[derive(Clone, Debug)]
+pub enum CellValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl CellValue {
+    pub fn from_sql(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Null => CellValue::Null,
+            ValueRef::Integer(i) => CellValue::Integer(i),
+            ValueRef::Real(f) => CellValue::Real(f),
+            ValueRef::Text(s) => CellValue::Text(String::from_utf8_lossy(s).into_owned()),
+            ValueRef::Blob(b) => CellValue::Blob(b.to_vec()),
+        }
+    }
+
+    /// Text shown for the cell in the results table.
+    pub fn display(&self) -> Cow<'_, str> {
+        match self {
+            CellValue::Null => Cow::Borrowed("NULL"),
+            CellValue::Integer(i) => Cow::Owned(i.to_string()),
+            CellValue::Real(f) => Cow::Owned(f.to_string()),
+            CellValue::Text(s) => Cow::Borrowed(s),
+            CellValue::Blob(_) => Cow::Borrowed("<BLOB>"),
+        }
+    }
+
+    /// Text for a results table cell: `display`, except empty text shows as `∅` so it can't be
+    /// mistaken for a cell that hasn't been drawn. SQLite stores booleans as integers, so in a
+    /// `boolean` column (see `is_boolean_type`) `0` and `1` show as `false` and `true`.
+    pub fn table_text(&self, boolean: bool) -> Cow<'_, str> {
+        match self {
+            CellValue::Text(s) if s.is_empty() => Cow::Borrowed(EMPTY_TEXT_MARKER),
+            CellValue::Integer(0) if boolean => Cow::Borrowed("false"),
+            CellValue::Integer(1) if boolean => Cow::Borrowed("true"),
+            other => other.display(),
+        }
+    }
+
+    /// The complete value for the cell popup: text split at newlines, numbers as displayed, and
+    /// blobs as a size line followed by a hex dump with printable ASCII alongside.
+    pub fn detail_lines(&self) -> Vec<String> {
+        match self {
+            CellValue::Text(s) => s.split('\n').map(ToString::to_string).collect(),
+            CellValue::Blob(bytes) => {
+                let mut lines = vec![format!("BLOB, {} bytes", bytes.len())];
+                for (i, chunk) in bytes.chunks(16).enumerate() {
+                    let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                    let ascii: String = chunk
+                        .iter()
+                        .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                        .collect();
+                    lines.push(format!("{:08x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii));
+                }
+                lines
+            },
+            other => vec![other.display().into_owned()],
+        }
+    }
+
+    /// Text put on the clipboard: like `display`, but NULL copies as an empty string.
+    pub fn copy_text(&self) -> Cow<'_, str> {
+        match self {
+            CellValue::Null => Cow::Borrowed(""),
+            other => other.display(),
+        }
+    }
+}
+
+/// Whether a column's declared type names a boolean (`BOOLEAN`, `BOOL`). SQLite gives such
+/// columns numeric affinity, so only the declaration tells them apart from other integers.
+pub fn is_boolean_type(decl_type: &str) -> bool {
+    decl_type.to_ascii_uppercase().contains("BOOL")
+}
+
+// Reals compare by bit pattern so rows can be hashed and diffed; NaN equals itself here
+impl PartialEq for CellValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CellValue::Null, CellValue::Null) => true,
+            (CellValue::Integer(a), CellValue::Integer(b)) => a == b,
+            (CellValue::Real(a), CellValue::Real(b)) => a.to_bits() == b.to_bits(),
+            (CellValue::Text(a), CellValue::Text(b)) => a == b,
+            (CellValue::Blob(a), CellValue::Blob(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for CellValue {}
+
+impl Hash for CellValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            CellValue::Null => {},
+            CellValue::Integer(i) => i.hash(state),
+            CellValue::Real(f) => f.to_bits().hash(state),
+            CellValue::Text(s) => s.hash(state),
+            CellValue::Blob(b) => b.hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_text_marks_empty_text_only() {
+        assert_eq!(CellValue::Text(String::new()).table_text(false), EMPTY_TEXT_MARKER);
+        assert_eq!(CellValue::Text(String::new()).display(), "");
+        assert_eq!(CellValue::Null.table_text(false), "NULL");
+        assert_eq!(CellValue::Text("NULL".to_string()).table_text(false), "NULL");
+    }
+
+    #[test]
+    fn table_text_names_booleans_in_boolean_columns() {
+        assert!(is_boolean_type("BOOLEAN") && is_boolean_type("bool"));
+        assert!(!is_boolean_type("INTEGER"));
+        assert_eq!(CellValue::Integer(1).table_text(true), "true");
+        assert_eq!(CellValue::Integer(0).table_text(true), "false");
+        assert_eq!(CellValue::Integer(2).table_text(true), "2");
+        assert_eq!(CellValue::Null.table_text(true), "NULL");
+        assert_eq!(CellValue::Integer(1).table_text(false), "1");
+    }
+
+    #[test]
+    fn keeps_sqlite_types_and_displays_them() {
+        let conn = rusqlite::Connection::open_in_memory().expect("in-memory database should open");
+        let row: Vec<CellValue> = conn
+            .query_row("select null, 42, 1.5, 'hi', x'00ff'", [], |row| {
+                (0..5).map(|i| Ok(CellValue::from_sql(row.get_ref(i)?))).collect()
+            })
+            .expect("row should read");
+        assert_eq!(
+            row,
+            vec![
+                CellValue::Null,
+                CellValue::Integer(42),
+                CellValue::Real(1.5),
+                CellValue::Text("hi".to_string()),
+                CellValue::Blob(vec![0, 255]),
+            ]
+        );
+        let shown: Vec<Cow<str>> = row.iter().map(CellValue::display).collect();
+        assert_eq!(shown, ["NULL", "42", "1.5", "hi", "<BLOB>"]);
+        assert_eq!(row[0].copy_text(), "");
+        assert_ne!(CellValue::Integer(1), CellValue::Text("1".to_string()));
+    }
+
+    #[test]
+    fn detail_lines_show_full_text_and_hex_dump_blobs() {
+        assert_eq!(CellValue::Text("a\n\nb".to_string()).detail_lines(), ["a", "", "b"]);
+        let blob = CellValue::Blob(b"squeal\x00\x01 sqlite blob!".to_vec());
+        assert_eq!(
+            blob.detail_lines(),
+            [
+                "BLOB, 21 bytes",
+                "00000000  73 71 75 65 61 6c 00 01 20 73 71 6c 69 74 65 20  |squeal.. sqlite |",
+                "00000010  62 6c 6f 62 21                                   |blob!|",
+            ]
+        );
+    }
+}